@@ -0,0 +1,12 @@
+-- Keys used to sign tokens (e.g. OpenID Connect ID tokens), the private keys are encrypted using the pepper.
+CREATE TABLE signing_key (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kid TEXT UNIQUE NOT NULL,
+    algorithm TEXT NOT NULL,
+    private_key TEXT NOT NULL,
+    private_key_nonces TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    retired_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
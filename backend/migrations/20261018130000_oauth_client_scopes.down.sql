@@ -0,0 +1 @@
+ALTER TABLE oauth_client DROP COLUMN allowed_scopes;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- The scopes that the client is allowed to request.
+ALTER TABLE oauth_client
+ADD COLUMN allowed_scopes TEXT[] NOT NULL DEFAULT '{openid,profile,email}';
@@ -0,0 +1,3 @@
+-- Clients which require PKCE must provide a code challenge when requesting authorization.
+ALTER TABLE oauth_client
+ADD COLUMN require_pkce BOOLEAN NOT NULL DEFAULT FALSE;
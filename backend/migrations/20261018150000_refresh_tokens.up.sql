@@ -0,0 +1,19 @@
+-- Refresh tokens issued to oauth clients, only the sha256 hash of the token is stored.
+-- Every refresh token belongs to a family which is created when the user authorizes the client,
+-- each use of a refresh token replaces it with a new one in the same family.
+CREATE TABLE refresh_token (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    token_hash TEXT UNIQUE NOT NULL,
+    family_id UUID NOT NULL,
+    account_id UUID NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    client_id TEXT NOT NULL REFERENCES oauth_client(client_id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    auth_time TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX refresh_token_family_id_idx ON refresh_token(family_id);
@@ -0,0 +1,11 @@
+ALTER TABLE oauth_client
+ADD COLUMN redirect_uri TEXT NOT NULL DEFAULT '';
+
+UPDATE oauth_client
+SET redirect_uri = COALESCE(redirect_uris[1], '');
+
+ALTER TABLE oauth_client
+ALTER COLUMN redirect_uri DROP DEFAULT;
+
+ALTER TABLE oauth_client
+DROP COLUMN redirect_uris;
@@ -0,0 +1,9 @@
+-- Clients may register several redirect URIs, e.g. for staging environments.
+ALTER TABLE oauth_client
+ADD COLUMN redirect_uris TEXT[] NOT NULL DEFAULT '{}';
+
+UPDATE oauth_client
+SET redirect_uris = ARRAY[redirect_uri];
+
+ALTER TABLE oauth_client
+DROP COLUMN redirect_uri;
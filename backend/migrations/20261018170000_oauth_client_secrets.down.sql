@@ -0,0 +1,11 @@
+-- The plaintext secrets can't be recovered, new secrets have to be handed out to the clients.
+ALTER TABLE oauth_client
+ADD COLUMN client_secret TEXT UNIQUE;
+
+UPDATE oauth_client
+SET client_secret = gen_random_uuid()::TEXT;
+
+ALTER TABLE oauth_client
+ALTER COLUMN client_secret SET NOT NULL;
+
+DROP TABLE oauth_client_secret;
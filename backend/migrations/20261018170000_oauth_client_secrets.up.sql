@@ -0,0 +1,17 @@
+-- Client secrets are stored as sha256 hashes (hex encoded), a client may have several secrets
+-- so that they can be rotated without downtime.
+CREATE TABLE oauth_client_secret (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    oauth_client_id UUID NOT NULL REFERENCES oauth_client(id) ON DELETE CASCADE,
+    secret_hash TEXT UNIQUE NOT NULL,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+INSERT INTO oauth_client_secret (oauth_client_id, secret_hash)
+SELECT id, upper(encode(sha256(convert_to(client_secret, 'UTF8')), 'hex'))
+FROM oauth_client;
+
+ALTER TABLE oauth_client
+DROP COLUMN client_secret;
@@ -0,0 +1,4 @@
+DROP TABLE oauth_consent;
+
+ALTER TABLE oauth_client
+DROP COLUMN first_party;
@@ -0,0 +1,15 @@
+-- First party clients are trusted and never ask the user for consent.
+ALTER TABLE oauth_client
+ADD COLUMN first_party BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The scopes an account has consented to share with a client,
+-- the user is asked again if the client requests any other scope.
+CREATE TABLE oauth_consent (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id UUID NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    client_id TEXT NOT NULL REFERENCES oauth_client(client_id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (account_id, client_id)
+);
@@ -0,0 +1,3 @@
+-- Where clients may ask for the user to be sent after logging out through the end session endpoint.
+ALTER TABLE oauth_client
+ADD COLUMN post_logout_redirect_uris TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+DROP TABLE backchannel_logout;
+
+ALTER TABLE oauth_client
+DROP COLUMN backchannel_logout_uri;
@@ -0,0 +1,18 @@
+-- Clients which want to be told when a user's session ends (OpenID Connect Back-Channel Logout).
+ALTER TABLE oauth_client
+ADD COLUMN backchannel_logout_uri TEXT;
+
+-- Logout tokens that still have to be delivered to clients, failed deliveries are retried.
+-- The account may have been deleted by the time the logout is delivered, so it isn't a foreign key.
+CREATE TABLE backchannel_logout (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id TEXT NOT NULL REFERENCES oauth_client(client_id) ON DELETE CASCADE,
+    account_id UUID NOT NULL,
+    sid TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX backchannel_logout_next_attempt_at_idx ON backchannel_logout(next_attempt_at);
@@ -0,0 +1,3 @@
+-- Clients registered through the dynamic registration endpoint manage their registration with this token (RFC 7592).
+ALTER TABLE oauth_client
+ADD COLUMN registration_token_hash TEXT;
@@ -0,0 +1,16 @@
+-- Which docker repositories accounts may access through a registry client,
+-- a rule applies either to a single account or to every account with the authority level.
+-- Repositories are matched exactly or by prefix if the rule ends with a '*'.
+CREATE TABLE registry_access_rule (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id TEXT NOT NULL REFERENCES oauth_client(client_id) ON DELETE CASCADE,
+    account_id UUID REFERENCES account(id) ON DELETE CASCADE,
+    authority AUTHORITY_LEVEL,
+    repository TEXT NOT NULL,
+    actions TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK ((account_id IS NULL) <> (authority IS NULL))
+);
+
+CREATE INDEX registry_access_rule_client_id_idx ON registry_access_rule(client_id);
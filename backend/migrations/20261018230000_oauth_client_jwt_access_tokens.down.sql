@@ -0,0 +1,2 @@
+ALTER TABLE oauth_client
+DROP COLUMN jwt_access_tokens;
@@ -0,0 +1,4 @@
+-- Clients whose access tokens are signed JWTs (RFC 9068) that resource servers can verify themselves,
+-- rather than opaque tokens which have to be looked up.
+ALTER TABLE oauth_client
+ADD COLUMN jwt_access_tokens BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+ALTER TABLE oauth_client
+DROP COLUMN require_pushed_authorization_requests;
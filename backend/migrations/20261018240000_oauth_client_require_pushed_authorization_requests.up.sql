@@ -0,0 +1,4 @@
+-- Clients which may only start authorization with a pushed authorization request (RFC 9126),
+-- so that the authorization parameters never pass through the browser.
+ALTER TABLE oauth_client
+ADD COLUMN require_pushed_authorization_requests BOOLEAN NOT NULL DEFAULT FALSE;
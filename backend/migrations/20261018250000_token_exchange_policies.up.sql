@@ -0,0 +1,11 @@
+-- Which downstream services a client may exchange access tokens for (RFC 8693),
+-- the audience is the client id of the service and the exchanged tokens get at most the policy's scopes.
+CREATE TABLE token_exchange_policy (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id TEXT NOT NULL REFERENCES oauth_client(client_id) ON DELETE CASCADE,
+    audience TEXT NOT NULL REFERENCES oauth_client(client_id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (client_id, audience)
+);
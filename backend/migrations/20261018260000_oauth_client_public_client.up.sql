@@ -0,0 +1,4 @@
+-- Public clients (token_endpoint_auth_method none) can't keep a secret, they have no secret
+-- and authenticate with only their client id, which is why they must use PKCE.
+ALTER TABLE oauth_client
+ADD COLUMN public_client BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Refresh tokens from docker registry logins are used without client authentication,
+-- so they are kept apart from the refresh tokens issued to oauth clients.
+ALTER TABLE refresh_token
+ADD COLUMN registry BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,7 @@
+-- New keys are published for a while before they are used to sign anything,
+-- keys that haven't been activated yet are only published.
+ALTER TABLE signing_key
+ADD COLUMN activated_at TIMESTAMPTZ;
+
+UPDATE signing_key
+SET activated_at = created_at;
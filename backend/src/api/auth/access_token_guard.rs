@@ -4,15 +4,27 @@ use rocket::{
     request::{FromRequest, Outcome},
     State,
 };
+use sqlx::Pool;
 
-use crate::services::oauth2_authorization_service::{AccessToken, ACCESS_TOKEN_KEY_REDIS_PREFIX};
-use crate::{api::oauth::access_token::TOKEN_TYPE_BEARER, services::redis_service};
+use crate::services::{
+    oauth2_authorization_service::{self, AccessToken},
+    signing_key_service::SigningKeyCache,
+};
+use crate::{api::oauth::access_token::TOKEN_TYPE_BEARER, db::DB, util::config::Config};
 
 #[derive(Debug)]
 pub struct AccessTokenAuth {
     pub access_token: AccessToken,
 }
 
+impl AccessTokenAuth {
+    /// Whether the token was issued to a client for itself (client credentials grant)
+    /// rather than on behalf of a user.
+    pub fn is_client_token(&self) -> bool {
+        self.access_token.account_id.is_none()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum AccessTokenError {
     #[error("Missing authorization header")]
@@ -21,6 +33,10 @@ pub enum AccessTokenError {
     InvalidAuthHeader,
     #[error("Redis error")]
     RedisError,
+    #[error("Failed to validate the access token")]
+    ValidationError,
+    #[error("The access token is meant for another audience")]
+    InvalidAudience,
 }
 
 const AUTH_HEADER_NAME: &str = "Authorization";
@@ -42,6 +58,19 @@ impl<'r> FromRequest<'r> for AccessTokenAuth {
             }
         };
 
+        // JWT access tokens are verified against our signing keys.
+        let (Some(db_pool), Some(config), Some(signing_keys)) = (
+            request.guard::<&State<Pool<DB>>>().await.succeeded(),
+            request.guard::<&State<Config>>().await.succeeded(),
+            request.guard::<&State<SigningKeyCache>>().await.succeeded(),
+        ) else {
+            error!("Failed to retrieve db pool, config or signing keys");
+            return Outcome::Error((
+                Status::InternalServerError,
+                AccessTokenError::ValidationError,
+            ));
+        };
+
         let auth_header = request.headers().get_one(AUTH_HEADER_NAME);
         let bearer_token = match auth_header {
             Some(s) => s.to_string(),
@@ -59,25 +88,35 @@ impl<'r> FromRequest<'r> for AccessTokenAuth {
             }
         };
 
-        let key = format!("{}:{}", ACCESS_TOKEN_KEY_REDIS_PREFIX, access_token);
-        let access_token: AccessToken =
-            match redis_service::redis_get_option::<AccessToken>(redis_pool, key).await {
-                Ok(Some(a)) => a,
-                Ok(None) => {
-                    println!("Invalid auth token {access_token}");
-                    return Outcome::Error((
-                        Status::Unauthorized,
-                        AccessTokenError::InvalidAuthHeader,
-                    ));
-                }
-                Err(e) => {
-                    error!("Failed to get access token from redis, err: {e}");
-                    return Outcome::Error((
-                        Status::InternalServerError,
-                        AccessTokenError::RedisError,
-                    ));
-                }
-            };
+        let access_token: AccessToken = match oauth2_authorization_service::find_access_token(
+            db_pool,
+            redis_pool,
+            config,
+            signing_keys,
+            access_token,
+        )
+        .await
+        {
+            Ok(Some(a)) => a,
+            Ok(None) => {
+                println!("Invalid auth token {access_token}");
+                return Outcome::Error((Status::Unauthorized, AccessTokenError::InvalidAuthHeader));
+            }
+            Err(e) => {
+                error!("Failed to validate access token, err: {e}");
+                return Outcome::Error((
+                    Status::InternalServerError,
+                    AccessTokenError::ValidationError,
+                ));
+            }
+        };
+
+        // Exchanged tokens are only accepted by the client they were exchanged for,
+        // not by our own endpoints.
+        if let Some(audience) = &access_token.audience {
+            info!("Rejected access token exchanged for audience {}", audience);
+            return Outcome::Error((Status::Unauthorized, AccessTokenError::InvalidAudience));
+        }
 
         Outcome::Success(AccessTokenAuth { access_token })
     }
@@ -9,6 +9,7 @@ use crate::{
 use super::session_guard::Session;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct AdminSession {
     pub session: Session,
     pub account: Account,
@@ -26,6 +26,8 @@ pub struct Session {
     pub id: String,
     pub expiration: DateTime<Utc>,
     pub account_id: Uuid,
+    /// When the user entered their credentials to create the session.
+    pub authenticated_at: DateTime<Utc>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -33,14 +35,22 @@ struct RedisSession {
     pub id: String,
     pub expiration: DateTime<Utc>,
     pub account_id: uuid::Uuid,
+    #[serde(default)]
+    pub authenticated_at: Option<DateTime<Utc>>,
 }
 
 impl From<RedisSession> for Session {
     fn from(s: RedisSession) -> Self {
+        // Sessions created before the authentication time was stored expire a fixed time after login.
+        let authenticated_at = s
+            .authenticated_at
+            .unwrap_or(s.expiration - Duration::days(SESSION_COOKIE_EXPIRATION_DAYS));
+
         Self {
             id: s.id,
             expiration: s.expiration,
             account_id: s.account_id,
+            authenticated_at,
         }
     }
 }
@@ -51,6 +61,7 @@ impl From<Session> for RedisSession {
             id: s.id,
             expiration: s.expiration,
             account_id: s.account_id,
+            authenticated_at: Some(s.authenticated_at),
         }
     }
 }
@@ -163,8 +174,9 @@ pub async fn set_session(
         .map(char::from)
         .collect();
 
+    let now = Utc::now();
     let time_until_expiration = Duration::days(SESSION_COOKIE_EXPIRATION_DAYS);
-    let expiration_time: DateTime<Utc> = Utc::now()
+    let expiration_time: DateTime<Utc> = now
         .checked_add_signed(time_until_expiration)
         .ok_or(SessionError::ExpirationTimeGeneration)?;
 
@@ -172,6 +184,7 @@ pub async fn set_session(
         id: session_id.clone(),
         expiration: expiration_time,
         account_id: uuid_from_sqlx(login_details.account_id),
+        authenticated_at: now,
     }
     .into();
 
@@ -202,6 +215,17 @@ pub async fn set_session(
     Ok(())
 }
 
+/// Removes the session from the cache, the session cookie has to be removed separately.
+pub async fn delete_session(
+    redis_pool: &State<Pool<RedisConnectionManager>>,
+    session_id: &str,
+) -> Result<(), SessionError> {
+    let key = format!("{}:{}", SESSIONS_KEY_PREFIX, session_id);
+    redis_service::redis_del(redis_pool, key)
+        .await
+        .or(Err(SessionError::SessionDeletion))
+}
+
 pub async fn delete_session_cookie<'r>(cookie_jar: &CookieJar<'r>) {
     if let Some(cookie) = cookie_jar.get_private(SESSION_COOKIE_KEY) {
         cookie_jar.remove_private(cookie);
@@ -209,10 +233,11 @@ pub async fn delete_session_cookie<'r>(cookie_jar: &CookieJar<'r>) {
     // If the result is none then the cookie doesn't exist and we are all good
 }
 
+/// Ends all of the account's sessions, returning the ids of the sessions.
 pub async fn reset_account_sessions(
     redis_pool: &State<Pool<RedisConnectionManager>>,
     account_id: Uuid,
-) -> Result<(), SessionError> {
+) -> Result<Vec<String>, SessionError> {
     let mut redis_conn = redis_pool.get().await.map_err(|err| {
         error!("Failed to get redis connection from pool, err {}", err);
         SessionError::RedisPoolError
@@ -227,8 +252,13 @@ pub async fn reset_account_sessions(
             SessionError::CacheReadError
         })?;
 
+    let session_keys: Vec<String> = list
+        .iter()
+        .map(|session_id| format!("{}:{}", SESSIONS_KEY_PREFIX, session_id))
+        .collect();
+
     redis_conn
-        .del::<Vec<String>, usize>(list)
+        .del::<Vec<String>, usize>(session_keys)
         .await
         .map_err(|err| {
             error!("Failed to delete sessions for account, err: {}", err);
@@ -243,5 +273,5 @@ pub async fn reset_account_sessions(
         SessionError::SessionDeletion
     })?;
 
-    Ok(())
+    Ok(list)
 }
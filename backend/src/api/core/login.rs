@@ -41,16 +41,24 @@ fn login_error(data: &mut BTreeMap<&str, String>, error: &str) -> Template {
     Template::render(LOGIN_TEMPLATE_NAME, &data)
 }
 
-#[get("/login?<return_to>")]
+/// The login hint prefills the email, oauth clients use it when they already know who is logging in.
+/// Users which are already logged in are only asked for their credentials again if
+/// the client has requested them to reauthenticate.
+#[get("/login?<return_to>&<login_hint>&<reauthenticate>")]
 pub async fn get_login_page(
     return_to: Option<String>,
+    login_hint: Option<String>,
+    reauthenticate: Option<bool>,
     session: Option<Session>,
 ) -> Either<Template, Redirect> {
-    if session.is_some() {
+    if session.is_some() && !reauthenticate.unwrap_or(false) {
         return Either::Right(Redirect::to(LOGIN_SUCCESSFUL_ADDRESS));
     }
 
     let mut data: BTreeMap<&str, String> = get_default_login_data();
+    if let Some(login_hint) = login_hint {
+        data.insert(EMAIL_KEY, login_hint);
+    }
     let return_to = if let Some(rt) = return_to {
         rt
     } else {
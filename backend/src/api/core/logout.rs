@@ -1,23 +1,29 @@
 use mobc_redis::RedisConnectionManager;
 use rocket::{http::CookieJar, response::Redirect, State};
+use sqlx::Pool;
 
 use crate::{
-    api::auth::session_guard::{delete_session_cookie, Session},
-    services::redis_service,
+    api::auth::session_guard::{delete_session, delete_session_cookie, Session},
+    db::DB,
+    services::{backchannel_logout_service, signing_key_service::SigningKeyCache},
+    util::config::Config,
 };
 
 #[post("/logout")]
 pub async fn post_logout(
     session: Option<Session>,
     cookies: &CookieJar<'_>,
+    db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
 ) -> Redirect {
     let session = match session {
         None => return Redirect::to("/"),
         Some(s) => s,
     };
 
-    if let Err(e) = redis_service::redis_del(redis_pool, session.id).await {
+    if let Err(e) = delete_session(redis_pool, &session.id).await {
         // This is bad but continue and at least try to remove the cookie from the user
         // We need to watch out for this error to avoid a memory-leak
         // (although one that can be solved by clearing the redis-db)
@@ -26,5 +32,18 @@ pub async fn post_logout(
 
     delete_session_cookie(cookies).await;
 
+    if let Err(e) = backchannel_logout_service::logout_session(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &session.id,
+        session.account_id,
+    )
+    .await
+    {
+        error!("Failed to send backchannel logouts, err: {}", e);
+    }
+
     Redirect::to("/")
 }
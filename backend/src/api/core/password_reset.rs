@@ -8,7 +8,10 @@ use sqlx::Pool;
 
 use crate::{
     db::DB,
-    services::reset_password_service::{self, ResetPasswordError},
+    services::{
+        reset_password_service::{self, ResetPasswordError},
+        signing_key_service::SigningKeyCache,
+    },
     util::config::Config,
 };
 
@@ -101,6 +104,7 @@ pub struct PasswordResetForm {
 #[post("/reset_password", data = "<reset_password>")]
 pub async fn post_reset_password(
     config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
     db_pool: &State<Pool<DB>>,
     reset_password: Form<PasswordResetForm>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
@@ -129,6 +133,7 @@ pub async fn post_reset_password(
 
     if let Err(e) = reset_password_service::update_password(
         config,
+        signing_keys,
         db_pool,
         redis_pool,
         reset_password.email.to_owned(),
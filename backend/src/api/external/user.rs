@@ -30,6 +30,14 @@ pub async fn get_user_info(
     access_token: AccessTokenAuth,
     db_pool: &State<Pool<DB>>,
 ) -> ResponseStatus<UserInfoResponse> {
+    if access_token.is_client_token() {
+        error!(
+            "Client {} tried to get user info using a client credentials token",
+            access_token.access_token.client_id
+        );
+        return ResponseStatus::err(Status::Forbidden, ErrMsg::ClientAccessToken);
+    }
+
     let user_info = match user_info_service::get_user_info(db_pool, access_token.access_token).await
     {
         Ok(user_info) => user_info,
@@ -2,6 +2,8 @@ use rocket::Route;
 
 pub mod me;
 pub mod oauth_client;
+pub mod registry_access_rule;
+pub mod token_exchange_policy;
 pub mod users;
 pub mod whitelist;
 
@@ -9,11 +11,27 @@ pub fn site_routes() -> Vec<Route> {
     routes![
         me::get_me,
         users::get_users,
+        users::post_logout_user,
         whitelist::get_whitelist,
         whitelist::add_email_to_whitelist,
         whitelist::delete_email_from_whitelist,
         oauth_client::get_oauth_clients,
         oauth_client::post_new_client,
         oauth_client::delete_client,
+        oauth_client::put_redirect_uris,
+        oauth_client::put_post_logout_redirect_uris,
+        oauth_client::put_first_party,
+        oauth_client::put_jwt_access_tokens,
+        oauth_client::put_require_pushed_authorization_requests,
+        oauth_client::put_backchannel_logout_uri,
+        oauth_client::get_client_secrets,
+        oauth_client::post_client_secret,
+        oauth_client::delete_client_secret,
+        registry_access_rule::get_registry_access_rules,
+        registry_access_rule::post_registry_access_rule,
+        registry_access_rule::delete_registry_access_rule,
+        token_exchange_policy::get_token_exchange_policies,
+        token_exchange_policy::post_token_exchange_policy,
+        token_exchange_policy::delete_token_exchange_policy,
     ]
 }
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use rocket::{http::Status, serde::json::Json, State};
 use serde::{Deserialize, Serialize};
 
@@ -7,6 +8,7 @@ use crate::{
         response::{EmptyResponse, ErrMsg, ResponseStatus},
     },
     db::DB,
+    models::{oauth_client::OauthClient, oauth_client_secret::OauthClientSecret},
     services::oauth_client_service::{self, OauthClientError},
 };
 
@@ -21,10 +23,37 @@ pub struct OauthClientsResponse {
 pub struct OauthClientResponse {
     client_name: String,
     client_id: String,
-    redirect_uri: String,
+    redirect_uris: Vec<String>,
+    scopes: Vec<String>,
+    require_pkce: bool,
+    public_client: bool,
+    first_party: bool,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+    jwt_access_tokens: bool,
+    require_pushed_authorization_requests: bool,
     id: String,
 }
 
+impl From<OauthClient> for OauthClientResponse {
+    fn from(client: OauthClient) -> Self {
+        OauthClientResponse {
+            client_name: client.client_name,
+            client_id: client.client_id,
+            redirect_uris: client.redirect_uris,
+            scopes: client.allowed_scopes,
+            require_pkce: client.require_pkce,
+            public_client: client.public_client,
+            first_party: client.first_party,
+            post_logout_redirect_uris: client.post_logout_redirect_uris,
+            backchannel_logout_uri: client.backchannel_logout_uri,
+            jwt_access_tokens: client.jwt_access_tokens,
+            require_pushed_authorization_requests: client.require_pushed_authorization_requests,
+            id: client.id.to_string(),
+        }
+    }
+}
+
 #[get("/oauth_clients")]
 pub async fn get_oauth_clients(
     db_pool: &State<sqlx::Pool<DB>>,
@@ -32,15 +61,7 @@ pub async fn get_oauth_clients(
 ) -> ResponseStatus<OauthClientsResponse> {
     match oauth_client_service::get_oauth_clients(db_pool).await {
         Ok(clients) => ResponseStatus::ok(OauthClientsResponse {
-            oauth_clients: clients
-                .into_iter()
-                .map(|client| OauthClientResponse {
-                    client_name: client.client_name,
-                    client_id: client.client_id,
-                    redirect_uri: client.redirect_uri,
-                    id: client.id.to_string(),
-                })
-                .collect(),
+            oauth_clients: clients.into_iter().map(|client| client.into()).collect(),
         }),
         Err(err) => {
             error!("Failed to get oauth_clients, err {}", err);
@@ -53,14 +74,25 @@ pub async fn get_oauth_clients(
 #[serde(rename_all = "camelCase")]
 pub struct NewClientRequest {
     pub client_name: String,
-    pub redirect_uri: String,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Option<Vec<String>>,
+    #[serde(default)]
+    pub require_pkce: bool,
+    #[serde(default)]
+    pub public_client: bool,
+    #[serde(default)]
+    pub first_party: bool,
+    #[serde(default)]
+    pub post_logout_redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub backchannel_logout_uri: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct NewClientResponse {
     pub client_id: String,
-    pub client_secret: String,
+    pub client_secret: Option<String>,
 }
 
 #[post("/oauth_clients", data = "<request>")]
@@ -72,14 +104,23 @@ pub async fn post_new_client(
     match oauth_client_service::create_oauth_client(
         db_pool,
         request.client_name.to_owned(),
-        request.redirect_uri.to_owned(),
+        request.redirect_uris.to_owned(),
+        request.scopes.to_owned(),
+        request.require_pkce,
+        request.public_client,
+        request.first_party,
+        request.post_logout_redirect_uris.to_owned(),
+        request.backchannel_logout_uri.to_owned(),
     )
     .await
     {
-        Ok(client) => ResponseStatus::ok(NewClientResponse {
+        Ok((client, client_secret)) => ResponseStatus::ok(NewClientResponse {
             client_id: client.client_id,
-            client_secret: client.client_secret,
+            client_secret,
         }),
+        Err(OauthClientError::InvalidRedirectUri) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidRedirectUri)
+        }
         Err(OauthClientError::ClientNameTaken) => {
             error!("The client name has already been taken");
             ResponseStatus::err(Status::UnprocessableEntity, ErrMsg::OauthClientNameTaken)
@@ -108,3 +149,324 @@ pub async fn delete_client(
         Err(_) => ResponseStatus::internal_err(),
     }
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectUrisRequest {
+    pub redirect_uris: Vec<String>,
+}
+
+#[put("/oauth_clients/<id>/redirect_uris", data = "<request>")]
+pub async fn put_redirect_uris(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<RedirectUrisRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<OauthClientResponse> {
+    match oauth_client_service::update_redirect_uris(
+        db_pool,
+        id,
+        request.into_inner().redirect_uris,
+    )
+    .await
+    {
+        Ok(client) => ResponseStatus::ok(client.into()),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::InvalidRedirectUri) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidRedirectUri)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to update redirect uris, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostLogoutRedirectUrisRequest {
+    pub post_logout_redirect_uris: Vec<String>,
+}
+
+#[put("/oauth_clients/<id>/post_logout_redirect_uris", data = "<request>")]
+pub async fn put_post_logout_redirect_uris(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<PostLogoutRedirectUrisRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<OauthClientResponse> {
+    match oauth_client_service::update_post_logout_redirect_uris(
+        db_pool,
+        id,
+        request.into_inner().post_logout_redirect_uris,
+    )
+    .await
+    {
+        Ok(client) => ResponseStatus::ok(client.into()),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::InvalidRedirectUri) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidRedirectUri)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to update post logout redirect uris, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackchannelLogoutUriRequest {
+    pub backchannel_logout_uri: Option<String>,
+}
+
+#[put("/oauth_clients/<id>/backchannel_logout_uri", data = "<request>")]
+pub async fn put_backchannel_logout_uri(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<BackchannelLogoutUriRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<OauthClientResponse> {
+    match oauth_client_service::update_backchannel_logout_uri(
+        db_pool,
+        id,
+        request.into_inner().backchannel_logout_uri,
+    )
+    .await
+    {
+        Ok(client) => ResponseStatus::ok(client.into()),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::InvalidRedirectUri) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidRedirectUri)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to update backchannel logout uri, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirstPartyRequest {
+    pub first_party: bool,
+}
+
+#[put("/oauth_clients/<id>/first_party", data = "<request>")]
+pub async fn put_first_party(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<FirstPartyRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<OauthClientResponse> {
+    match oauth_client_service::update_first_party(db_pool, id, request.first_party).await {
+        Ok(client) => ResponseStatus::ok(client.into()),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to update first party flag, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JwtAccessTokensRequest {
+    pub jwt_access_tokens: bool,
+}
+
+#[put("/oauth_clients/<id>/jwt_access_tokens", data = "<request>")]
+pub async fn put_jwt_access_tokens(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<JwtAccessTokensRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<OauthClientResponse> {
+    match oauth_client_service::update_jwt_access_tokens(db_pool, id, request.jwt_access_tokens)
+        .await
+    {
+        Ok(client) => ResponseStatus::ok(client.into()),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to update jwt access tokens flag, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequirePushedAuthorizationRequestsRequest {
+    pub require_pushed_authorization_requests: bool,
+}
+
+#[put(
+    "/oauth_clients/<id>/require_pushed_authorization_requests",
+    data = "<request>"
+)]
+pub async fn put_require_pushed_authorization_requests(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<RequirePushedAuthorizationRequestsRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<OauthClientResponse> {
+    match oauth_client_service::update_require_pushed_authorization_requests(
+        db_pool,
+        id,
+        request.require_pushed_authorization_requests,
+    )
+    .await
+    {
+        Ok(client) => ResponseStatus::ok(client.into()),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!(
+                "Failed to update require pushed authorization requests flag, err: {}",
+                err
+            );
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientSecretsResponse {
+    secrets: Vec<ClientSecretResponse>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientSecretResponse {
+    id: String,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<OauthClientSecret> for ClientSecretResponse {
+    fn from(secret: OauthClientSecret) -> Self {
+        ClientSecretResponse {
+            id: secret.id.to_string(),
+            created_at: secret.created_at,
+            expires_at: secret.expires_at,
+        }
+    }
+}
+
+#[get("/oauth_clients/<id>/secrets")]
+pub async fn get_client_secrets(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<ClientSecretsResponse> {
+    match oauth_client_service::get_client_secrets(db_pool, id).await {
+        Ok(secrets) => ResponseStatus::ok(ClientSecretsResponse {
+            secrets: secrets.into_iter().map(|secret| secret.into()).collect(),
+        }),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(err) => {
+            error!("Failed to get client secrets, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewClientSecretRequest {
+    /// The number of minutes that the client's current secrets remain valid,
+    /// if not provided they stay valid until they are deleted.
+    pub previous_secrets_expire_in_minutes: Option<i64>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NewClientSecretResponse {
+    id: String,
+    client_secret: String,
+}
+
+#[post("/oauth_clients/<id>/secrets", data = "<request>")]
+pub async fn post_client_secret(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<NewClientSecretRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<NewClientSecretResponse> {
+    match oauth_client_service::create_client_secret(
+        db_pool,
+        id,
+        request.previous_secrets_expire_in_minutes,
+    )
+    .await
+    {
+        Ok((secret, client_secret)) => ResponseStatus::ok(NewClientSecretResponse {
+            id: secret.id.to_string(),
+            client_secret,
+        }),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to create client secret, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[delete("/oauth_clients/<id>/secrets/<secret_id>")]
+pub async fn delete_client_secret(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    secret_id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<EmptyResponse> {
+    match oauth_client_service::delete_client_secret(db_pool, id, secret_id).await {
+        Ok(()) => ResponseStatus::<EmptyResponse>::ok_no_content(),
+        Err(OauthClientError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(OauthClientError::SecretIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientSecretId)
+        }
+        Err(err) => {
+            error!("Failed to delete client secret, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use rocket::{http::Status, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        auth::admin_session_guard::AdminSession,
+        response::{EmptyResponse, ErrMsg, ResponseStatus},
+    },
+    db::DB,
+    models::{authority::AuthorityLevel, registry_access_rule::RegistryAccessRule},
+    services::registry_access_service::{self, RegistryAccessError},
+};
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryAccessRulesResponse {
+    rules: Vec<RegistryAccessRuleResponse>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryAccessRuleResponse {
+    id: String,
+    account_id: Option<String>,
+    authority: Option<AuthorityLevel>,
+    repository: String,
+    actions: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<RegistryAccessRule> for RegistryAccessRuleResponse {
+    fn from(rule: RegistryAccessRule) -> Self {
+        RegistryAccessRuleResponse {
+            id: rule.id.to_string(),
+            account_id: rule.account_id.map(|account_id| account_id.to_string()),
+            authority: rule.authority,
+            repository: rule.repository,
+            actions: rule.actions,
+            created_at: rule.created_at,
+        }
+    }
+}
+
+#[get("/oauth_clients/<id>/registry_access_rules")]
+pub async fn get_registry_access_rules(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<RegistryAccessRulesResponse> {
+    match registry_access_service::get_rules(db_pool, id).await {
+        Ok(rules) => ResponseStatus::ok(RegistryAccessRulesResponse {
+            rules: rules.into_iter().map(|rule| rule.into()).collect(),
+        }),
+        Err(RegistryAccessError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(RegistryAccessError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to get registry access rules, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewRegistryAccessRuleRequest {
+    /// Either an account or an authority level that the rule applies to.
+    pub account_id: Option<String>,
+    pub authority: Option<AuthorityLevel>,
+    pub repository: String,
+    pub actions: Vec<String>,
+}
+
+#[post("/oauth_clients/<id>/registry_access_rules", data = "<request>")]
+pub async fn post_registry_access_rule(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<NewRegistryAccessRuleRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<RegistryAccessRuleResponse> {
+    let request = request.into_inner();
+    match registry_access_service::create_rule(
+        db_pool,
+        id,
+        request.account_id,
+        request.authority,
+        request.repository,
+        request.actions,
+    )
+    .await
+    {
+        Ok(rule) => ResponseStatus::ok(rule.into()),
+        Err(RegistryAccessError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(RegistryAccessError::InvalidRule) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidRegistryAccessRule)
+        }
+        Err(RegistryAccessError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(RegistryAccessError::AccountNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidAccountId)
+        }
+        Err(err) => {
+            error!("Failed to create registry access rule, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[delete("/oauth_clients/<id>/registry_access_rules/<rule_id>")]
+pub async fn delete_registry_access_rule(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    rule_id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<EmptyResponse> {
+    match registry_access_service::delete_rule(db_pool, id, rule_id).await {
+        Ok(()) => ResponseStatus::<EmptyResponse>::ok_no_content(),
+        Err(RegistryAccessError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(RegistryAccessError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(RegistryAccessError::RuleIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidRegistryAccessRuleId)
+        }
+        Err(err) => {
+            error!("Failed to delete registry access rule, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
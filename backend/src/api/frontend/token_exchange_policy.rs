@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use rocket::{http::Status, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        auth::admin_session_guard::AdminSession,
+        response::{EmptyResponse, ErrMsg, ResponseStatus},
+    },
+    db::DB,
+    models::token_exchange_policy::TokenExchangePolicy,
+    services::token_exchange_service::{self, TokenExchangeError},
+};
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenExchangePoliciesResponse {
+    policies: Vec<TokenExchangePolicyResponse>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenExchangePolicyResponse {
+    id: String,
+    audience: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<TokenExchangePolicy> for TokenExchangePolicyResponse {
+    fn from(policy: TokenExchangePolicy) -> Self {
+        TokenExchangePolicyResponse {
+            id: policy.id.to_string(),
+            audience: policy.audience,
+            scopes: policy.scopes,
+            created_at: policy.created_at,
+        }
+    }
+}
+
+#[get("/oauth_clients/<id>/token_exchange_policies")]
+pub async fn get_token_exchange_policies(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<TokenExchangePoliciesResponse> {
+    match token_exchange_service::get_policies(db_pool, id).await {
+        Ok(policies) => ResponseStatus::ok(TokenExchangePoliciesResponse {
+            policies: policies.into_iter().map(|policy| policy.into()).collect(),
+        }),
+        Err(TokenExchangeError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(TokenExchangeError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to get token exchange policies, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTokenExchangePolicyRequest {
+    /// The client id of the service that tokens may be exchanged for.
+    pub audience: String,
+    pub scopes: Vec<String>,
+}
+
+#[post("/oauth_clients/<id>/token_exchange_policies", data = "<request>")]
+pub async fn post_token_exchange_policy(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    request: Json<NewTokenExchangePolicyRequest>,
+    _admin_session: AdminSession,
+) -> ResponseStatus<TokenExchangePolicyResponse> {
+    let request = request.into_inner();
+    match token_exchange_service::create_policy(db_pool, id, request.audience, request.scopes).await
+    {
+        Ok(policy) => ResponseStatus::ok(policy.into()),
+        Err(TokenExchangeError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(TokenExchangeError::InvalidPolicy) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidTokenExchangePolicy)
+        }
+        Err(TokenExchangeError::PolicyExists) => {
+            ResponseStatus::err(Status::Conflict, ErrMsg::TokenExchangePolicyExists)
+        }
+        Err(TokenExchangeError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(err) => {
+            error!("Failed to create token exchange policy, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+#[delete("/oauth_clients/<id>/token_exchange_policies/<policy_id>")]
+pub async fn delete_token_exchange_policy(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    policy_id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<EmptyResponse> {
+    match token_exchange_service::delete_policy(db_pool, id, policy_id).await {
+        Ok(()) => ResponseStatus::<EmptyResponse>::ok_no_content(),
+        Err(TokenExchangeError::InvalidId) => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid)
+        }
+        Err(TokenExchangeError::ClientIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidClientId)
+        }
+        Err(TokenExchangeError::PolicyIdNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidTokenExchangePolicyId)
+        }
+        Err(err) => {
+            error!("Failed to delete token exchange policy, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
@@ -1,11 +1,19 @@
-use rocket::State;
+use mobc_redis::RedisConnectionManager;
+use rocket::{http::Status, State};
 use serde::Serialize;
 
 use crate::{
-    api::{auth::admin_session_guard::AdminSession, response::ResponseStatus},
+    api::{
+        auth::admin_session_guard::AdminSession,
+        response::{EmptyResponse, ErrMsg, ResponseStatus},
+    },
     db::DB,
     models::authority::AuthorityLevel,
-    services::users_service,
+    services::{
+        signing_key_service::SigningKeyCache,
+        users_service::{self, UsersError},
+    },
+    util::config::Config,
 };
 
 #[derive(Serialize, Clone)]
@@ -16,6 +24,7 @@ pub struct Users {
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
+    id: String,
     first_name: String,
     last_name: String,
     authority: AuthorityLevel,
@@ -38,6 +47,7 @@ pub async fn get_users(
         users: accs
             .into_iter()
             .map(|acc| User {
+                id: acc.id.to_string(),
                 first_name: acc.first_name,
                 last_name: acc.last_name,
                 authority: acc.authority,
@@ -45,3 +55,26 @@ pub async fn get_users(
             .collect::<Vec<User>>(),
     })
 }
+
+/// Logs the user out everywhere, e.g. if their account has been compromised.
+#[post("/users/<id>/logout")]
+pub async fn post_logout_user(
+    db_pool: &State<sqlx::Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
+    id: String,
+    _admin_session: AdminSession,
+) -> ResponseStatus<EmptyResponse> {
+    match users_service::logout_user(db_pool, redis_pool, config, signing_keys, id).await {
+        Ok(()) => ResponseStatus::<EmptyResponse>::ok_no_content(),
+        Err(UsersError::InvalidId) => ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidUuid),
+        Err(UsersError::AccountNotFound) => {
+            ResponseStatus::err(Status::NotFound, ErrMsg::InvalidAccountId)
+        }
+        Err(err) => {
+            error!("Failed to log out user, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
@@ -5,7 +5,7 @@ use chrono::Utc;
 use mobc_redis::RedisConnectionManager;
 use rocket::{
     form::Form,
-    http::Status,
+    http::{RawStr, Status},
     request::{self, FromRequest},
     serde::json::Json,
     Request, Response, State,
@@ -17,13 +17,23 @@ use crate::{
     db::DB,
     services::{
         login_service,
-        oauth2_authorization_service::{self, AccessToken, Oauth2Error},
+        oauth2_authorization_service::{self, AccessToken, IssuedTokens},
+        registry_access_service::{self, RegistryToken},
+        signing_key_service::SigningKeyCache,
     },
-    util::config::Config,
+    util::{config::Config, scope},
 };
 use rocket::response::Responder;
 
-const GRANT_TYPE_AUTHORIZATION_CODE: &str = "authorization_code";
+use super::oauth_error::{OauthErrorCode, OauthErrorResponse};
+
+pub const GRANT_TYPE_AUTHORIZATION_CODE: &str = "authorization_code";
+pub const GRANT_TYPE_REFRESH_TOKEN: &str = "refresh_token";
+pub const GRANT_TYPE_CLIENT_CREDENTIALS: &str = "client_credentials";
+pub const GRANT_TYPE_DEVICE_CODE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+pub const GRANT_TYPE_TOKEN_EXCHANGE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const GRANT_TYPE_PASSWORD: &str = "password";
+const ACCESS_TYPE_OFFLINE: &str = "offline";
 const HEADER_CACHE_CONTROL: &str = "Cache-Control";
 const HEADER_PRAGMA: &str = "Pragma";
 
@@ -31,14 +41,36 @@ const NO_CACHE: &str = "no-cache";
 const NO_STORE: &str = "no-store";
 
 pub const TOKEN_TYPE_BEARER: &str = "Bearer";
+// The only token type the token exchange grant accepts and issues (RFC 8693 section 3).
+const TOKEN_TYPE_ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
 
 #[derive(FromForm, Debug)]
 pub struct AccessTokenRequest {
     grant_type: String,
-    redirect_uri: String,
-    code: String,
-    client_id: String,
-    client_secret: String,
+    // Either sent in the form (client_secret_post) or in the auth header (client_secret_basic)
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    // authorization_code grant
+    redirect_uri: Option<String>,
+    code: Option<String>,
+    code_verifier: Option<String>,
+    // refresh_token grant
+    refresh_token: Option<String>,
+    // refresh_token and client_credentials grants
+    scope: Option<String>,
+    // device_code grant
+    device_code: Option<String>,
+    // token-exchange grant, the client itself is the actor so actor tokens aren't accepted
+    subject_token: Option<String>,
+    subject_token_type: Option<String>,
+    audience: Option<String>,
+    requested_token_type: Option<String>,
+    actor_token: Option<String>,
+    // Docker registry token requests, which use the password and refresh_token grants
+    service: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    access_type: Option<String>,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -46,16 +78,32 @@ pub struct AccessTokenSuccessResponse {
     access_token: String,
     expires_in: u32,
     token_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issued_token_type: Option<String>,
 }
 
-pub enum AccessTokenResponse {
-    Success(Json<AccessTokenSuccessResponse>),
-    Error(String, Status),
+/// The response expected by docker clients (Docker token authentication specification),
+/// the token is repeated as access_token for OAuth2 compatible clients.
+#[derive(Serialize, Clone, Debug)]
+pub struct RegistryTokenResponse {
+    token: String,
+    access_token: String,
+    expires_in: i64,
+    issued_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
 }
 
-#[derive(Serialize, Clone, Debug)]
-pub struct AccessTokenErrorResponse {
-    message: String,
+pub enum AccessTokenResponse {
+    Success(Json<AccessTokenSuccessResponse>),
+    Registry(Json<RegistryTokenResponse>),
+    Error(OauthErrorResponse),
 }
 
 // Second step in the oauth2 authorization flow.
@@ -63,76 +111,290 @@ pub struct AccessTokenErrorResponse {
 pub async fn post_access_token(
     db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
     request: Form<AccessTokenRequest>,
+    auth_header: Option<AuthHeader>,
 ) -> AccessTokenResponse {
-    if request.grant_type != GRANT_TYPE_AUTHORIZATION_CODE {
-        return AccessTokenResponse::Error(
-            String::from("Invalid grant type"),
-            Status::UnprocessableEntity,
-        );
+    // Docker clients identify the registry rather than authenticating as a client,
+    // any other request is handled as an oauth request even if it happens to include a service.
+    if let (GRANT_TYPE_PASSWORD | GRANT_TYPE_REFRESH_TOKEN, Some(service)) =
+        (request.grant_type.as_str(), &request.service)
+    {
+        match registry_access_service::is_registry(db_pool, service).await {
+            Ok(true) => {
+                return post_registry_token(
+                    db_pool,
+                    config,
+                    signing_keys,
+                    service.clone(),
+                    &request,
+                )
+                .await
+            }
+            Ok(false) => {}
+            Err(err) => return AccessTokenResponse::Error(err.into()),
+        }
     }
 
-    let access_token = match oauth2_authorization_service::get_access_token(
-        db_pool,
-        redis_pool,
+    let (client_id, client_secret) = match client_credentials(
+        auth_header,
         request.client_id.clone(),
         request.client_secret.clone(),
-        request.redirect_uri.clone(),
-        request.code.clone(),
-    )
-    .await
-    {
-        Ok(access_token) => access_token,
-        Err(Oauth2Error::NoClientWithId) => {
-            return AccessTokenResponse::Error(
-                String::from("Invalid client ID"),
-                Status::BadRequest,
-            );
+    ) {
+        Ok(credentials) => credentials,
+        Err(msg) => {
+            return AccessTokenResponse::Error(OauthErrorResponse::new(
+                OauthErrorCode::InvalidRequest,
+                &msg,
+            ))
         }
-        Err(Oauth2Error::InvalidRedirectUri) => {
-            return AccessTokenResponse::Error(
-                String::from("Invalid redirect URI"),
-                Status::BadRequest,
-            );
+    };
+
+    let result = match request.grant_type.as_str() {
+        GRANT_TYPE_AUTHORIZATION_CODE => {
+            let (Some(redirect_uri), Some(code)) = (&request.redirect_uri, &request.code) else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Missing redirect URI or code",
+                ));
+            };
+
+            oauth2_authorization_service::get_access_token(
+                db_pool,
+                redis_pool,
+                config,
+                signing_keys,
+                client_id.clone(),
+                client_secret.clone(),
+                redirect_uri.clone(),
+                code.clone(),
+                request.code_verifier.clone(),
+            )
+            .await
         }
-        Err(Oauth2Error::InvalidClientSecret) => {
-            return AccessTokenResponse::Error(
-                String::from("Invalid client secret"),
-                Status::BadRequest,
-            );
+        GRANT_TYPE_REFRESH_TOKEN => {
+            let Some(refresh_token) = &request.refresh_token else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Missing refresh token",
+                ));
+            };
+
+            oauth2_authorization_service::refresh_access_token(
+                db_pool,
+                redis_pool,
+                config,
+                signing_keys,
+                client_id.clone(),
+                client_secret.clone(),
+                refresh_token.clone(),
+                request.scope.as_deref().map(scope::parse_scopes),
+            )
+            .await
         }
-        Err(Oauth2Error::InvalidCode) => {
-            return AccessTokenResponse::Error(String::from("Invalid code"), Status::BadRequest);
+        GRANT_TYPE_CLIENT_CREDENTIALS => {
+            let Some(client_secret) = &client_secret else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidClient,
+                    "Missing client secret",
+                ));
+            };
+
+            oauth2_authorization_service::get_client_credentials_token(
+                db_pool,
+                redis_pool,
+                config,
+                signing_keys,
+                client_id.clone(),
+                client_secret.clone(),
+                request.scope.as_deref().map(scope::parse_scopes),
+            )
+            .await
+            .map(IssuedTokens::from)
         }
-        Err(err) => {
-            error!("Failed to get access token, err: {}", err);
-            return AccessTokenResponse::Error(
-                String::from("An internal server error occurred"),
-                Status::BadRequest,
-            );
+        GRANT_TYPE_DEVICE_CODE => {
+            let Some(device_code) = &request.device_code else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Missing device code",
+                ));
+            };
+
+            oauth2_authorization_service::get_device_code_token(
+                db_pool,
+                redis_pool,
+                config,
+                signing_keys,
+                client_id.clone(),
+                client_secret.clone(),
+                device_code.clone(),
+            )
+            .await
+        }
+        GRANT_TYPE_TOKEN_EXCHANGE => {
+            let Some(client_secret) = &client_secret else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidClient,
+                    "Missing client secret",
+                ));
+            };
+
+            let (Some(subject_token), Some(audience)) = (&request.subject_token, &request.audience)
+            else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Missing subject token or audience",
+                ));
+            };
+
+            if request.subject_token_type.as_deref() != Some(TOKEN_TYPE_ACCESS_TOKEN)
+                || request
+                    .requested_token_type
+                    .as_deref()
+                    .is_some_and(|token_type| token_type != TOKEN_TYPE_ACCESS_TOKEN)
+                || request.actor_token.is_some()
+            {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Only access tokens can be exchanged, without an actor token",
+                ));
+            }
+
+            oauth2_authorization_service::exchange_token(
+                db_pool,
+                redis_pool,
+                config,
+                signing_keys,
+                client_id.clone(),
+                client_secret.clone(),
+                subject_token.clone(),
+                audience.clone(),
+                request.scope.as_deref().map(scope::parse_scopes),
+            )
+            .await
+            .map(IssuedTokens::from)
+        }
+        _ => {
+            return AccessTokenResponse::Error(OauthErrorResponse::new(
+                OauthErrorCode::UnsupportedGrantType,
+                "Unsupported grant type",
+            ));
         }
     };
 
-    let access_token_response = access_token.into();
+    let issued_tokens = match result {
+        Ok(issued_tokens) => issued_tokens,
+        Err(err) => return AccessTokenResponse::Error(err.into()),
+    };
+
+    let access_token_response = issued_tokens.into();
     AccessTokenResponse::Success(Json(access_token_response))
 }
 
+async fn post_registry_token(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
+    service: String,
+    request: &AccessTokenRequest,
+) -> AccessTokenResponse {
+    let scopes = request.scope.as_slice();
+
+    let result = match request.grant_type.as_str() {
+        GRANT_TYPE_PASSWORD => {
+            let (Some(username), Some(password)) = (&request.username, &request.password) else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Missing username or password",
+                ));
+            };
+
+            let login_details = match login_service::validate_login(
+                config,
+                db_pool,
+                username.clone(),
+                password.clone(),
+            )
+            .await
+            {
+                Ok(login_details) => login_details,
+                Err(err) => {
+                    error!("Failed to validate login for registry token, err: {err}");
+                    return AccessTokenResponse::Error(OauthErrorResponse::new(
+                        OauthErrorCode::InvalidGrant,
+                        "Invalid username or password",
+                    ));
+                }
+            };
+
+            registry_access_service::get_registry_token(
+                db_pool,
+                config,
+                signing_keys,
+                service,
+                login_details.account_id,
+                scopes,
+                request.access_type.as_deref() == Some(ACCESS_TYPE_OFFLINE),
+            )
+            .await
+        }
+        GRANT_TYPE_REFRESH_TOKEN => {
+            let Some(refresh_token) = &request.refresh_token else {
+                return AccessTokenResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    "Missing refresh token",
+                ));
+            };
+
+            registry_access_service::refresh_registry_token(
+                db_pool,
+                config,
+                signing_keys,
+                service,
+                refresh_token,
+                scopes,
+            )
+            .await
+        }
+        _ => {
+            return AccessTokenResponse::Error(OauthErrorResponse::new(
+                OauthErrorCode::UnsupportedGrantType,
+                "Unsupported grant type",
+            ));
+        }
+    };
+
+    match result {
+        Ok(token) => AccessTokenResponse::Registry(Json(token.into())),
+        Err(err) => AccessTokenResponse::Error(err.into()),
+    }
+}
+
 impl<'r> Responder<'r, 'static> for AccessTokenResponse {
     fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
-        match self {
-            AccessTokenResponse::Success(content) => {
-                let mut response = Response::build_from(content.respond_to(request)?);
-                response.status(Status::Ok);
-                response.raw_header(HEADER_CACHE_CONTROL, NO_STORE);
-                response.raw_header(HEADER_PRAGMA, NO_CACHE);
-                response.ok()
-            }
-            AccessTokenResponse::Error(msg, status) => {
-                let err_response = Json(AccessTokenErrorResponse { message: msg });
-                let mut response = Response::build_from(err_response.respond_to(request)?);
-                response.status(status);
-                response.ok()
-            }
+        let content = match self {
+            AccessTokenResponse::Success(content) => content.respond_to(request)?,
+            AccessTokenResponse::Registry(content) => content.respond_to(request)?,
+            AccessTokenResponse::Error(err) => return err.respond_to(request),
+        };
+
+        let mut response = Response::build_from(content);
+        response.status(Status::Ok);
+        response.raw_header(HEADER_CACHE_CONTROL, NO_STORE);
+        response.raw_header(HEADER_PRAGMA, NO_CACHE);
+        response.ok()
+    }
+}
+
+impl From<RegistryToken> for RegistryTokenResponse {
+    fn from(value: RegistryToken) -> Self {
+        RegistryTokenResponse {
+            access_token: value.token.clone(),
+            token: value.token,
+            expires_in: (value.expiration - value.issued_at).num_seconds(),
+            issued_at: value.issued_at.to_rfc3339(),
+            refresh_token: value.refresh_token,
         }
     }
 }
@@ -146,14 +408,77 @@ impl From<AccessToken> for AccessTokenSuccessResponse {
         }
         let expires_in = expires_in as u32;
 
+        let scope = if value.scopes.is_empty() {
+            None
+        } else {
+            Some(scope::format_scopes(&value.scopes))
+        };
+
+        // Only exchanged tokens have an audience, the exchange response must say what was issued.
+        let issued_token_type = value
+            .audience
+            .is_some()
+            .then(|| TOKEN_TYPE_ACCESS_TOKEN.to_string());
+
         AccessTokenSuccessResponse {
             access_token: value.access_token,
             expires_in,
             token_type: TOKEN_TYPE_BEARER.to_string(),
+            scope,
+            refresh_token: None,
+            id_token: None,
+            issued_token_type,
+        }
+    }
+}
+
+impl From<IssuedTokens> for AccessTokenSuccessResponse {
+    fn from(value: IssuedTokens) -> Self {
+        AccessTokenSuccessResponse {
+            refresh_token: value.refresh_token,
+            id_token: value.id_token,
+            ..value.access_token.into()
         }
     }
 }
 
+/// Resolves the client's credentials, which may be sent either in the Authorization header
+/// or in the request body but not both (RFC 6749 section 2.3.1).
+pub fn client_credentials(
+    auth_header: Option<AuthHeader>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Result<(String, Option<String>), String> {
+    let Some(auth_header) = auth_header else {
+        return client_id
+            .map(|client_id| (client_id, client_secret))
+            .ok_or(String::from("Missing client ID"));
+    };
+
+    if client_secret.is_some() {
+        return Err(String::from(
+            "The client must only use one authentication method",
+        ));
+    }
+
+    // The credentials are form-urlencoded before being put in the header.
+    let (Ok(header_client_id), Ok(header_client_secret)) = (
+        RawStr::new(&auth_header.username).url_decode(),
+        RawStr::new(&auth_header.password).url_decode(),
+    ) else {
+        return Err(String::from("Malformed auth header"));
+    };
+
+    if client_id.is_some_and(|client_id| client_id != header_client_id) {
+        return Err(String::from("Client ID did not match the auth header"));
+    }
+
+    Ok((
+        header_client_id.into_owned(),
+        Some(header_client_secret.into_owned()),
+    ))
+}
+
 pub struct AuthHeader {
     username: String,
     password: String,
@@ -165,7 +490,7 @@ impl<'r> FromRequest<'r> for AuthHeader {
 
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
         let Some(auth_header) = req.headers().get_one("authorization") else {
-            error!("Got request with missing auth header");
+            debug!("Got request without an auth header");
             return request::Outcome::Error((
                 Status::Unauthorized,
                 "Missing auth header".to_string(),
@@ -222,17 +547,18 @@ impl<'r> FromRequest<'r> for AuthHeader {
     }
 }
 
-// For docker flow
-#[get("/token?<service>&<offline_token>&<client_id>&<scope>")]
+/// Issues docker registry tokens (Docker token authentication specification),
+/// the scope parameter may be repeated to request access to several resources.
+// Docker also sends a client_id, which identifies the docker client and isn't needed here.
+#[get("/token?<service>&<offline_token>&<scope>")]
 pub async fn get_access_token(
     db_pool: &State<sqlx::Pool<DB>>,
-    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
     config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
     auth_header: AuthHeader,
     service: String,
     offline_token: Option<bool>,
-    client_id: Option<String>,
-    scope: Option<String>,
+    scope: Vec<String>,
 ) -> AccessTokenResponse {
     info!(
         "Access token for user {}, service {service} and scopes {scope:?}",
@@ -250,30 +576,25 @@ pub async fn get_access_token(
         Ok(s) => s,
         Err(e) => {
             error!("Failed to validate login for user due to err: {e}");
-            return AccessTokenResponse::Error(
-                String::from("Failed to authenticate user"),
-                Status::Unauthorized,
-            );
+            return AccessTokenResponse::Error(OauthErrorResponse::new(
+                OauthErrorCode::InvalidClient,
+                "Failed to authenticate user",
+            ));
         }
     };
 
-    let access_token = match oauth2_authorization_service::get_access_token_basic_auth(
-        redis_pool,
+    match registry_access_service::get_registry_token(
+        db_pool,
+        config,
+        signing_keys,
         service,
         login_details.account_id,
+        &scope,
+        offline_token.unwrap_or(false),
     )
     .await
     {
-        Ok(token) => token,
-        Err(e) => {
-            error!("Failed to generate access token due to error {e:?}");
-            return AccessTokenResponse::Error(
-                String::from("Failed to generate access token"),
-                Status::InternalServerError,
-            );
-        }
-    };
-
-    let response = access_token.into();
-    AccessTokenResponse::Success(Json(response))
+        Ok(token) => AccessTokenResponse::Registry(Json(token.into())),
+        Err(err) => AccessTokenResponse::Error(err.into()),
+    }
 }
@@ -1,5 +1,8 @@
+use chrono::{DateTime, Utc};
 use mobc_redis::RedisConnectionManager;
-use rocket::{http::Status, response::Redirect, State};
+use rocket::{form::Form, http::Status, response::Redirect, Either, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
 use sqlx::Pool;
 
 use crate::{
@@ -8,82 +11,522 @@ use crate::{
         response::{ErrMsg, ResponseStatus},
     },
     db::DB,
-    services::oauth2_authorization_service::{self, Oauth2Error},
+    services::{
+        oauth2_authorization_service::{
+            self, Authorization, ConsentPrompt, ConsentRequest, Oauth2Error,
+        },
+        pushed_authorization_service::{self, AuthorizationParameters, PushedAuthorizationError},
+    },
+    util::{
+        config::Config,
+        pkce::{self, CodeChallenge, CodeChallengeMethod},
+        scope::{self, SCOPE_EMAIL, SCOPE_OPENID, SCOPE_PROFILE},
+    },
 };
 
 use crate::api::core::login::rocket_uri_macro_get_login_page;
 
-const RESPONSE_TYPE_CODE: &str = "code";
+use super::oauth_error::{OauthErrorCode, OauthErrorResponse};
+
+pub const RESPONSE_TYPE_CODE: &str = "code";
+
+const PROMPT_NONE: &str = "none";
+const PROMPT_LOGIN: &str = "login";
+const PROMPT_CONSENT: &str = "consent";
+
+const CONSENT_TEMPLATE_NAME: &str = "consent";
+
+#[derive(FromForm, UriDisplayQuery, Debug)]
+pub struct AuthorizationRequest {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    scope: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    prompt: Option<String>,
+    // The maximum number of seconds since the user last entered their credentials,
+    // parsed after the redirect uri has been validated so that errors can be sent to the client.
+    max_age: Option<String>,
+    login_hint: Option<String>,
+    // Echoed in the ID token to let the client detect replayed tokens.
+    nonce: Option<String>,
+    // Added by us when sending the user to log in again, only a login after it satisfies the request.
+    reauthentication: Option<String>,
+}
+
+/// The values of the space separated prompt parameter (OpenID Connect Core section 3.1.2.1),
+/// unknown values are ignored.
+#[derive(Debug, Default)]
+struct Prompt {
+    none: bool,
+    login: bool,
+    consent: bool,
+}
+
+impl Prompt {
+    fn parse(prompt: Option<&str>) -> Prompt {
+        let mut parsed = Prompt::default();
+        for value in prompt.unwrap_or_default().split_whitespace() {
+            match value {
+                PROMPT_NONE => parsed.none = true,
+                PROMPT_LOGIN => parsed.login = true,
+                PROMPT_CONSENT => parsed.consent = true,
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    fn consent_prompt(&self) -> ConsentPrompt {
+        if self.none {
+            ConsentPrompt::Never
+        } else if self.consent {
+            ConsentPrompt::Always
+        } else {
+            ConsentPrompt::IfRequired
+        }
+    }
+}
+
+impl AuthorizationRequest {
+    fn from_pushed(client_id: String, parameters: AuthorizationParameters) -> Self {
+        AuthorizationRequest {
+            response_type: parameters.response_type,
+            client_id,
+            redirect_uri: parameters.redirect_uri,
+            state: parameters.state,
+            scope: parameters.scope,
+            code_challenge: parameters.code_challenge,
+            code_challenge_method: parameters.code_challenge_method,
+            prompt: parameters.prompt,
+            max_age: parameters.max_age,
+            login_hint: parameters.login_hint,
+            nonce: parameters.nonce,
+            reauthentication: parameters.reauthentication,
+        }
+    }
+
+    fn to_pushed(&self) -> AuthorizationParameters {
+        AuthorizationParameters {
+            response_type: self.response_type.clone(),
+            redirect_uri: self.redirect_uri.clone(),
+            state: self.state.clone(),
+            scope: self.scope.clone(),
+            code_challenge: self.code_challenge.clone(),
+            code_challenge_method: self.code_challenge_method.clone(),
+            prompt: self.prompt.clone(),
+            max_age: self.max_age.clone(),
+            login_hint: self.login_hint.clone(),
+            nonce: self.nonce.clone(),
+            reauthentication: self.reauthentication.clone(),
+        }
+    }
+}
 
 /// First step in the oauth2 authorization flow.
-#[get("/authorize?<response_type>&<client_id>&<redirect_uri>&<state>")]
+/// Errors are only sent back to the client once its redirect uri has been validated,
+/// before that the user is shown the error directly (RFC 6749 section 4.1.2.1).
+#[get("/authorize?<request..>", rank = 2)]
 pub async fn get_authorization(
     db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
-    response_type: String,
+    config: &State<Config>,
+    request: AuthorizationRequest,
+    session: Option<Session>,
+) -> Result<Either<Template, Redirect>, ResponseStatus<()>> {
+    authorize(db_pool, redis_pool, config, request, None, session).await
+}
+
+/// The authorization flow for parameters the client has pushed beforehand (RFC 9126 section 4).
+#[get("/authorize?<client_id>&<request_uri>", rank = 1)]
+pub async fn get_pushed_authorization(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
     client_id: String,
-    redirect_uri: String,
-    state: String,
+    request_uri: String,
     session: Option<Session>,
-) -> Result<Redirect, ResponseStatus<()>> {
-    if response_type != RESPONSE_TYPE_CODE {
-        return Err(ResponseStatus::err(
-            Status::UnprocessableEntity,
-            ErrMsg::InvalidResponseType,
-        ));
+) -> Result<Either<Template, Redirect>, ResponseStatus<()>> {
+    let parameters = match pushed_authorization_service::get_pushed_authorization(
+        redis_pool,
+        &client_id,
+        &request_uri,
+    )
+    .await
+    {
+        Ok(parameters) => parameters,
+        Err(PushedAuthorizationError::InvalidRequestUri) => {
+            return Err(ResponseStatus::err(
+                Status::BadRequest,
+                ErrMsg::InvalidRequestUri,
+            ))
+        }
+        Err(err) => {
+            error!("Failed to get pushed authorization request, err: {}", err);
+            return Err(ResponseStatus::internal_err());
+        }
+    };
+
+    let request = AuthorizationRequest::from_pushed(client_id, parameters);
+    authorize(
+        db_pool,
+        redis_pool,
+        config,
+        request,
+        Some(request_uri),
+        session,
+    )
+    .await
+}
+
+async fn authorize(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    request: AuthorizationRequest,
+    request_uri: Option<String>,
+    session: Option<Session>,
+) -> Result<Either<Template, Redirect>, ResponseStatus<()>> {
+    let client = match oauth2_authorization_service::get_client_for_redirect_uri(
+        db_pool,
+        &request.client_id,
+        &request.redirect_uri,
+    )
+    .await
+    {
+        Ok(client) => client,
+        Err(err) => return Err(invalid_client_response(&request.client_id, err)),
+    };
+
+    let require_pushed_authorization_requests = config.require_pushed_authorization_requests
+        || client.require_pushed_authorization_requests;
+    if require_pushed_authorization_requests && request_uri.is_none() {
+        return Ok(Either::Right(error_redirect(
+            &request.redirect_uri,
+            &request.state,
+            OauthErrorResponse::new(
+                OauthErrorCode::InvalidRequest,
+                "The client requires pushed authorization requests",
+            ),
+        )));
+    }
+
+    if request.response_type != RESPONSE_TYPE_CODE {
+        return Ok(Either::Right(error_redirect(
+            &request.redirect_uri,
+            &request.state,
+            OauthErrorResponse::new(
+                OauthErrorCode::UnsupportedResponseType,
+                "Only the code response type is supported",
+            ),
+        )));
     }
 
+    let prompt = Prompt::parse(request.prompt.as_deref());
+    if prompt.none && (prompt.login || prompt.consent) {
+        return Ok(Either::Right(error_redirect(
+            &request.redirect_uri,
+            &request.state,
+            OauthErrorResponse::new(
+                OauthErrorCode::InvalidRequest,
+                "The none prompt can't be combined with other prompts",
+            ),
+        )));
+    }
+
+    let max_age = match request.max_age.as_deref().map(str::parse::<u32>) {
+        None => None,
+        Some(Ok(max_age)) => Some(max_age),
+        Some(Err(_)) => {
+            return Ok(Either::Right(error_redirect(
+                &request.redirect_uri,
+                &request.state,
+                OauthErrorResponse::new(OauthErrorCode::InvalidRequest, "Invalid max_age"),
+            )));
+        }
+    };
+
+    // Both prompt=login and max_age=0 require the user to log in again (OpenID Connect Core section 3.1.2.1),
+    // which no session from before the request satisfies however recent it is.
+    let login_required = prompt.login || max_age == Some(0);
+    let reauthentication_started_at = match &request.reauthentication {
+        Some(reauthentication_id) if login_required => {
+            match oauth2_authorization_service::take_reauthentication(
+                redis_pool,
+                &request.client_id,
+                reauthentication_id,
+            )
+            .await
+            {
+                Ok(started_at) => started_at,
+                Err(err) => {
+                    error!("Failed to get reauthentication, err: {}", err);
+                    return Err(ResponseStatus::internal_err());
+                }
+            }
+        }
+        _ => None,
+    };
+
     let session = match session {
-        Some(s) => s,
-        None => {
-            let return_to = format!(
-                "/api/oauth/{}",
-                uri!(get_authorization(
-                    response_type,
-                    client_id,
-                    redirect_uri,
-                    state
+        Some(s) if is_authenticated(&s, login_required, reauthentication_started_at, max_age) => s,
+        _ if prompt.none => {
+            return Ok(Either::Right(error_redirect(
+                &request.redirect_uri,
+                &request.state,
+                OauthErrorResponse::new(OauthErrorCode::LoginRequired, "The user has to log in"),
+            )));
+        }
+        session => {
+            // The user is sent back here with the same parameters once they have logged in,
+            // along with a new reauthentication if the login has to be a fresh one.
+            let reauthentication = if login_required {
+                match oauth2_authorization_service::start_reauthentication(
+                    redis_pool,
+                    request.client_id.clone(),
+                )
+                .await
+                {
+                    Ok(reauthentication_id) => Some(reauthentication_id),
+                    Err(err) => {
+                        error!("Failed to start reauthentication, err: {}", err);
+                        return Err(ResponseStatus::internal_err());
+                    }
+                }
+            } else {
+                None
+            };
+            let request = AuthorizationRequest {
+                reauthentication,
+                ..request
+            };
+            let login_hint = request.login_hint.clone();
+
+            // Pushed parameters stay out of the url, they are updated where they are stored instead.
+            let return_to = match request_uri {
+                Some(request_uri) => {
+                    if let Err(err) = pushed_authorization_service::update_pushed_authorization(
+                        redis_pool,
+                        request.client_id.clone(),
+                        &request_uri,
+                        request.to_pushed(),
+                    )
+                    .await
+                    {
+                        error!(
+                            "Failed to update pushed authorization request, err: {}",
+                            err
+                        );
+                        return Err(ResponseStatus::internal_err());
+                    }
+
+                    format!(
+                        "/api/oauth/{}",
+                        uri!(get_pushed_authorization(request.client_id, request_uri))
+                    )
+                }
+                None => format!("/api/oauth/{}", uri!(get_authorization(request))),
+            };
+
+            let login_uri = format!(
+                "/api/core/{}",
+                uri!(get_login_page(
+                    Some(return_to),
+                    login_hint,
+                    session.is_some().then_some(true)
                 ))
                 .to_string()
             );
 
-            let login_uri = format!(
-                "/api/core/{}",
-                uri!(get_login_page(Some(return_to))).to_string()
+            return Ok(Either::Right(Redirect::found(login_uri)));
+        }
+    };
+
+    // The request uri can only be used once, now that the user won't be sent back to it.
+    if let Some(request_uri) = &request_uri {
+        if let Err(err) =
+            pushed_authorization_service::remove_pushed_authorization(redis_pool, request_uri).await
+        {
+            error!(
+                "Failed to remove pushed authorization request, err: {}",
+                err
             );
+            return Err(ResponseStatus::internal_err());
+        }
+    }
 
-            return Ok(Redirect::found(login_uri));
+    let scopes = request.scope.as_deref().map(scope::parse_scopes);
+
+    let code_challenge = match &request.code_challenge {
+        Some(challenge) => {
+            let method = CodeChallengeMethod::parse(request.code_challenge_method.as_deref());
+            match method {
+                Some(method) if pkce::is_valid_code(challenge) => Some(CodeChallenge {
+                    challenge: challenge.clone(),
+                    method,
+                }),
+                _ => {
+                    return Ok(Either::Right(error_redirect(
+                        &request.redirect_uri,
+                        &request.state,
+                        Oauth2Error::InvalidCodeChallenge.into(),
+                    )))
+                }
+            }
         }
+        None => None,
     };
 
-    let url = match oauth2_authorization_service::get_auth_token(
+    match oauth2_authorization_service::get_auth_token(
         db_pool,
         redis_pool,
-        client_id.clone(),
-        redirect_uri,
-        state,
-        session.account_id,
+        request.client_id.clone(),
+        request.redirect_uri.clone(),
+        request.state.clone(),
+        scopes,
+        code_challenge,
+        request.nonce.clone(),
+        &session,
+        prompt.consent_prompt(),
     )
     .await
     {
-        Ok(url) => url,
-        Err(Oauth2Error::NoClientWithId) => {
-            error!("No client with id '{}'", client_id);
-            return Err(ResponseStatus::err(
-                Status::BadRequest,
-                ErrMsg::InvalidClientId,
-            ));
+        Ok(Authorization::Redirect(url)) => Ok(Either::Right(Redirect::found(url))),
+        Ok(Authorization::ConsentRequired(consent_request)) => Ok(Either::Left(Template::render(
+            CONSENT_TEMPLATE_NAME,
+            ConsentTemplateData::from(consent_request),
+        ))),
+        // The client may have been changed since it was validated above.
+        Err(err @ (Oauth2Error::NoClientWithId | Oauth2Error::InvalidRedirectUri)) => {
+            Err(invalid_client_response(&request.client_id, err))
+        }
+        Err(err) => Ok(Either::Right(error_redirect(
+            &request.redirect_uri,
+            &request.state,
+            err.into(),
+        ))),
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ConsentTemplateData {
+    consent_id: String,
+    client_name: String,
+    scopes: Vec<String>,
+}
+
+impl From<ConsentRequest> for ConsentTemplateData {
+    fn from(consent_request: ConsentRequest) -> Self {
+        ConsentTemplateData {
+            consent_id: consent_request.consent_id,
+            client_name: consent_request.client_name,
+            scopes: consent_request
+                .scopes
+                .iter()
+                .map(|scope| scope_description(scope))
+                .collect(),
         }
-        Err(Oauth2Error::InvalidRedirectUri) => {
+    }
+}
+
+pub fn scope_description(scope: &str) -> String {
+    match scope {
+        SCOPE_OPENID => String::from("Sign you in with your account"),
+        SCOPE_PROFILE => String::from("View your name"),
+        SCOPE_EMAIL => String::from("View your email address"),
+        scope => format!("Use the '{}' scope", scope),
+    }
+}
+
+#[derive(FromForm, Debug)]
+pub struct ConsentForm {
+    consent_id: String,
+    approve: bool,
+}
+
+/// The user's answer to the consent screen.
+#[post("/authorize", data = "<form>")]
+pub async fn post_authorization_consent(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    form: Form<ConsentForm>,
+    session: Session,
+) -> Result<Redirect, ResponseStatus<()>> {
+    let pending = match oauth2_authorization_service::take_pending_authorization(
+        redis_pool,
+        &form.consent_id,
+        session.account_id,
+    )
+    .await
+    {
+        Ok(pending) => pending,
+        Err(Oauth2Error::InvalidConsentRequest) => {
             return Err(ResponseStatus::err(
                 Status::BadRequest,
-                ErrMsg::InvalidRedirectUri,
+                ErrMsg::InvalidConsentRequest,
             ))
         }
         Err(err) => {
-            panic!("An oauth2 error occurred, err: {err}");
+            error!("Failed to get pending authorization, err: {}", err);
+            return Err(ResponseStatus::internal_err());
         }
     };
 
-    Ok(Redirect::found(url))
+    if !form.approve {
+        return Ok(error_redirect(
+            &pending.redirect_uri,
+            &pending.state,
+            OauthErrorResponse::new(
+                OauthErrorCode::AccessDenied,
+                "The user denied the authorization request",
+            ),
+        ));
+    }
+
+    let redirect_uri = pending.redirect_uri.clone();
+    let state = pending.state.clone();
+    match oauth2_authorization_service::approve_authorization(db_pool, redis_pool, pending).await {
+        Ok(url) => Ok(Redirect::found(url)),
+        Err(err) => Ok(error_redirect(&redirect_uri, &state, err.into())),
+    }
+}
+
+/// Whether the session is recent enough for the request,
+/// a required login has to have happened after the reauthentication was started.
+fn is_authenticated(
+    session: &Session,
+    login_required: bool,
+    reauthentication_started_at: Option<DateTime<Utc>>,
+    max_age: Option<u32>,
+) -> bool {
+    if login_required {
+        return reauthentication_started_at
+            .is_some_and(|started_at| session.authenticated_at > started_at);
+    }
+
+    !max_age.is_some_and(|max_age| {
+        (Utc::now() - session.authenticated_at).num_seconds() > i64::from(max_age)
+    })
+}
+
+fn invalid_client_response(client_id: &str, err: Oauth2Error) -> ResponseStatus<()> {
+    match err {
+        Oauth2Error::NoClientWithId => {
+            error!("No client with id '{}'", client_id);
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidClientId)
+        }
+        Oauth2Error::InvalidRedirectUri => {
+            ResponseStatus::err(Status::BadRequest, ErrMsg::InvalidRedirectUri)
+        }
+        err => {
+            error!("Failed to validate oauth client, err: {}", err);
+            ResponseStatus::internal_err()
+        }
+    }
+}
+
+fn error_redirect(redirect_uri: &str, state: &str, err: OauthErrorResponse) -> Redirect {
+    Redirect::found(err.to_redirect_uri(redirect_uri, Some(state)))
 }
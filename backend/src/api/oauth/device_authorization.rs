@@ -0,0 +1,215 @@
+use chrono::Utc;
+use mobc_redis::RedisConnectionManager;
+use rocket::{form::Form, response::Redirect, serde::json::Json, Either, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
+use sqlx::Pool;
+
+use crate::{
+    api::{auth::session_guard::Session, core::login::rocket_uri_macro_get_login_page},
+    db::DB,
+    services::{
+        device_authorization_service::{self, DeviceAuthorizationError},
+        oauth2_authorization_service::{self, Oauth2Error},
+    },
+    util::{config::Config, scope},
+};
+
+use super::{
+    access_token::{client_credentials, AuthHeader},
+    authorize::scope_description,
+    oauth_error::{OauthErrorCode, OauthErrorResponse},
+};
+
+const DEVICE_TEMPLATE_NAME: &str = "device";
+
+const ERR_INVALID_USER_CODE: &str = "The code is invalid or has expired";
+const ERR_INTERNAL: &str = "An internal error occurred";
+const DEVICE_APPROVED: &str = "The device has been connected, you can now return to it";
+const DEVICE_DENIED: &str = "The device has been denied access";
+
+#[derive(FromForm, Debug)]
+pub struct DeviceAuthorizationRequest {
+    // Either sent in the form (client_secret_post) or in the auth header (client_secret_basic)
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    scope: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeviceAuthorizationSuccessResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: String,
+    expires_in: i64,
+    interval: i64,
+}
+
+#[derive(Responder, Debug)]
+pub enum DeviceAuthorizationResponse {
+    #[response(status = 200)]
+    Success(Json<DeviceAuthorizationSuccessResponse>),
+    Error(OauthErrorResponse),
+}
+
+/// Device authorization endpoint (RFC 8628 section 3.1), the device then asks the user to visit the
+/// verification uri while polling the token endpoint.
+#[post("/device_authorization", data = "<request>")]
+pub async fn post_device_authorization(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    request: Form<DeviceAuthorizationRequest>,
+    auth_header: Option<AuthHeader>,
+) -> DeviceAuthorizationResponse {
+    let request = request.into_inner();
+
+    let (client_id, client_secret) =
+        match client_credentials(auth_header, request.client_id, request.client_secret) {
+            Ok(credentials) => credentials,
+            Err(msg) => {
+                return DeviceAuthorizationResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    &msg,
+                ))
+            }
+        };
+
+    let device_authorization = match oauth2_authorization_service::start_device_authorization(
+        db_pool,
+        redis_pool,
+        client_id,
+        client_secret,
+        request.scope.as_deref().map(scope::parse_scopes),
+    )
+    .await
+    {
+        Ok(device_authorization) => device_authorization,
+        Err(err) => return DeviceAuthorizationResponse::Error(err.into()),
+    };
+
+    let user_code = device_authorization_service::format_user_code(&device_authorization.user_code);
+    let verification_uri = format!("{}/api/oauth/device", config.backend_address);
+
+    DeviceAuthorizationResponse::Success(Json(DeviceAuthorizationSuccessResponse {
+        device_code: device_authorization.device_code,
+        verification_uri_complete: format!("{}?user_code={}", verification_uri, user_code),
+        verification_uri,
+        user_code,
+        expires_in: (device_authorization.expiration - Utc::now()).num_seconds(),
+        interval: device_authorization.interval_seconds,
+    }))
+}
+
+#[derive(Serialize, Debug, Default)]
+struct DeviceTemplateData {
+    user_code: Option<String>,
+    /// Only set once the user code has been looked up, the user is then asked to approve the device.
+    client_name: Option<String>,
+    scopes: Vec<String>,
+    error: Option<&'static str>,
+    info: Option<&'static str>,
+}
+
+/// The page where the user enters the code shown on their device,
+/// and is then shown which client is asking for which scopes before approving it.
+#[get("/device?<user_code>")]
+pub async fn get_device_page(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    user_code: Option<String>,
+    session: Option<Session>,
+) -> Either<Template, Redirect> {
+    if session.is_none() {
+        let return_to = format!("/api/oauth/{}", uri!(get_device_page(user_code)));
+        let login_uri = format!(
+            "/api/core/{}",
+            uri!(get_login_page(Some(return_to), _, _)).to_string()
+        );
+        return Either::Right(Redirect::found(login_uri));
+    }
+
+    let Some(user_code) = user_code else {
+        return Either::Left(Template::render(
+            DEVICE_TEMPLATE_NAME,
+            DeviceTemplateData::default(),
+        ));
+    };
+
+    let data = match oauth2_authorization_service::get_device_verification(
+        db_pool, redis_pool, &user_code,
+    )
+    .await
+    {
+        Ok(verification) => DeviceTemplateData {
+            user_code: Some(verification.user_code),
+            client_name: Some(verification.client_name),
+            scopes: verification
+                .scopes
+                .iter()
+                .map(|scope| scope_description(scope))
+                .collect(),
+            ..Default::default()
+        },
+        Err(err) => DeviceTemplateData {
+            user_code: Some(user_code),
+            error: Some(device_error_message(err)),
+            ..Default::default()
+        },
+    };
+
+    Either::Left(Template::render(DEVICE_TEMPLATE_NAME, data))
+}
+
+#[derive(FromForm)]
+pub struct DeviceVerificationForm {
+    user_code: String,
+    approve: bool,
+}
+
+#[post("/device", data = "<form>")]
+pub async fn post_device_page(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    form: Form<DeviceVerificationForm>,
+    session: Session,
+) -> Template {
+    let data = match oauth2_authorization_service::complete_device_verification(
+        db_pool,
+        redis_pool,
+        &form.user_code,
+        &session,
+        form.approve,
+    )
+    .await
+    {
+        Ok(()) if form.approve => DeviceTemplateData {
+            info: Some(DEVICE_APPROVED),
+            ..Default::default()
+        },
+        Ok(()) => DeviceTemplateData {
+            info: Some(DEVICE_DENIED),
+            ..Default::default()
+        },
+        Err(err) => DeviceTemplateData {
+            user_code: Some(form.user_code.clone()),
+            error: Some(device_error_message(err)),
+            ..Default::default()
+        },
+    };
+
+    Template::render(DEVICE_TEMPLATE_NAME, data)
+}
+
+fn device_error_message(err: Oauth2Error) -> &'static str {
+    match err {
+        Oauth2Error::DeviceAuthorizationError(
+            DeviceAuthorizationError::InvalidUserCode | DeviceAuthorizationError::ExpiredToken,
+        ) => ERR_INVALID_USER_CODE,
+        err => {
+            error!("Failed to verify device authorization, err: {}", err);
+            ERR_INTERNAL
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use mobc_redis::RedisConnectionManager;
+use rocket::{form::Form, serde::json::Json, State};
+use serde::Serialize;
+use sqlx::Pool;
+
+use crate::{
+    api::oauth::access_token::TOKEN_TYPE_BEARER,
+    db::DB,
+    services::{
+        oauth2_authorization_service::{self, AccessToken, Actor},
+        signing_key_service::SigningKeyCache,
+    },
+    util::{config::Config, scope},
+};
+
+use super::{
+    access_token::{client_credentials, AuthHeader},
+    oauth_error::{OauthErrorCode, OauthErrorResponse},
+};
+
+#[derive(FromForm, Debug)]
+pub struct IntrospectionRequest {
+    // Only access tokens can be introspected, so any token_type_hint is ignored.
+    token: String,
+    // Either sent in the form (client_secret_post) or in the auth header (client_secret_basic)
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct IntrospectionResponse {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+    // The clients acting on behalf of the subject of an exchanged token (RFC 8693 section 4.1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>,
+}
+
+#[derive(Responder, Debug)]
+pub enum IntrospectResponse {
+    #[response(status = 200)]
+    Success(Json<IntrospectionResponse>),
+    Error(OauthErrorResponse),
+}
+
+/// Lets resource servers validate access tokens (RFC 7662), requires the caller to authenticate as a client.
+#[post("/introspect", data = "<request>")]
+pub async fn post_introspect(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
+    request: Form<IntrospectionRequest>,
+    auth_header: Option<AuthHeader>,
+) -> IntrospectResponse {
+    let request = request.into_inner();
+
+    let (client_id, client_secret) =
+        match client_credentials(auth_header, request.client_id, request.client_secret) {
+            Ok(credentials) => credentials,
+            Err(msg) => {
+                return IntrospectResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    &msg,
+                ))
+            }
+        };
+
+    // Only confidential clients may introspect tokens.
+    let Some(client_secret) = client_secret else {
+        return IntrospectResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::InvalidClient,
+            "Missing client secret",
+        ));
+    };
+
+    match oauth2_authorization_service::introspect_token(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        client_id,
+        client_secret,
+        request.token,
+    )
+    .await
+    {
+        Ok(Some(access_token)) => IntrospectResponse::Success(Json(access_token.into())),
+        Ok(None) => IntrospectResponse::Success(Json(IntrospectionResponse::default())),
+        Err(err) => IntrospectResponse::Error(err.into()),
+    }
+}
+
+impl From<AccessToken> for IntrospectionResponse {
+    fn from(value: AccessToken) -> Self {
+        IntrospectionResponse {
+            active: true,
+            scope: Some(scope::format_scopes(&value.scopes)),
+            client_id: Some(value.client_id),
+            sub: value.account_id.map(|account_id| account_id.to_string()),
+            token_type: Some(TOKEN_TYPE_BEARER.to_string()),
+            exp: Some(value.expiration.timestamp()),
+            iat: Some(value.issued_at.timestamp()),
+            aud: value.audience,
+            act: value.act,
+        }
+    }
+}
@@ -2,11 +2,29 @@ use rocket::Route;
 
 pub mod access_token;
 pub mod authorize;
+pub mod device_authorization;
+pub mod introspect;
+pub mod oauth_error;
+pub mod pushed_authorization;
+pub mod register;
+pub mod revoke;
 
 pub fn oauth_routes() -> Vec<Route> {
     routes![
         authorize::get_authorization,
+        authorize::get_pushed_authorization,
+        authorize::post_authorization_consent,
         access_token::post_access_token,
         access_token::get_access_token,
+        revoke::post_revoke,
+        introspect::post_introspect,
+        pushed_authorization::post_pushed_authorization,
+        device_authorization::post_device_authorization,
+        device_authorization::get_device_page,
+        device_authorization::post_device_page,
+        register::post_register,
+        register::get_registration,
+        register::put_registration,
+        register::delete_registration,
     ]
 }
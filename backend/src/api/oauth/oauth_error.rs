@@ -0,0 +1,223 @@
+use rocket::{
+    http::{RawStr, Status},
+    response::Responder,
+    serde::json::Json,
+    Request, Response,
+};
+use serde::Serialize;
+
+use crate::api::oauth::access_token::TOKEN_TYPE_BEARER;
+use crate::services::{
+    device_authorization_service::DeviceAuthorizationError,
+    oauth2_authorization_service::Oauth2Error,
+    pushed_authorization_service::PushedAuthorizationError,
+    refresh_token_service::RefreshTokenError, registry_access_service::RegistryAccessError,
+    token_exchange_service::TokenExchangeError,
+};
+
+const HEADER_CACHE_CONTROL: &str = "Cache-Control";
+const HEADER_WWW_AUTHENTICATE: &str = "WWW-Authenticate";
+const NO_STORE: &str = "no-store";
+
+const INTERNAL_ERROR_DESCRIPTION: &str = "An internal server error occurred";
+
+/// Error codes defined by RFC 6749 section 4.1.2.1 and 5.2, RFC 6750 section 3.1, RFC 7591 section 3.2.2,
+/// RFC 8628 section 3.5, RFC 8693 section 2.2.2 and OpenID Connect Core section 3.1.2.6.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OauthErrorCode {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnsupportedGrantType,
+    UnsupportedResponseType,
+    InvalidScope,
+    AccessDenied,
+    ServerError,
+    AuthorizationPending,
+    SlowDown,
+    ExpiredToken,
+    LoginRequired,
+    ConsentRequired,
+    InvalidToken,
+    InsufficientScope,
+    InvalidRedirectUri,
+    InvalidClientMetadata,
+    InvalidTarget,
+}
+
+impl OauthErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OauthErrorCode::InvalidRequest => "invalid_request",
+            OauthErrorCode::InvalidClient => "invalid_client",
+            OauthErrorCode::InvalidGrant => "invalid_grant",
+            OauthErrorCode::UnsupportedGrantType => "unsupported_grant_type",
+            OauthErrorCode::UnsupportedResponseType => "unsupported_response_type",
+            OauthErrorCode::InvalidScope => "invalid_scope",
+            OauthErrorCode::AccessDenied => "access_denied",
+            OauthErrorCode::ServerError => "server_error",
+            OauthErrorCode::AuthorizationPending => "authorization_pending",
+            OauthErrorCode::SlowDown => "slow_down",
+            OauthErrorCode::ExpiredToken => "expired_token",
+            OauthErrorCode::LoginRequired => "login_required",
+            OauthErrorCode::ConsentRequired => "consent_required",
+            OauthErrorCode::InvalidToken => "invalid_token",
+            OauthErrorCode::InsufficientScope => "insufficient_scope",
+            OauthErrorCode::InvalidRedirectUri => "invalid_redirect_uri",
+            OauthErrorCode::InvalidClientMetadata => "invalid_client_metadata",
+            OauthErrorCode::InvalidTarget => "invalid_target",
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self {
+            OauthErrorCode::InvalidClient | OauthErrorCode::InvalidToken => Status::Unauthorized,
+            OauthErrorCode::InsufficientScope => Status::Forbidden,
+            OauthErrorCode::ServerError => Status::InternalServerError,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+/// The error body returned by the oauth endpoints (RFC 6749 section 5.2).
+#[derive(Serialize, Clone, Debug)]
+pub struct OauthErrorResponse {
+    pub error: OauthErrorCode,
+    pub error_description: String,
+}
+
+impl OauthErrorResponse {
+    pub fn new(error: OauthErrorCode, error_description: &str) -> Self {
+        OauthErrorResponse {
+            error,
+            error_description: error_description.to_string(),
+        }
+    }
+
+    pub fn server_error() -> Self {
+        OauthErrorResponse::new(OauthErrorCode::ServerError, INTERNAL_ERROR_DESCRIPTION)
+    }
+
+    /// Appends the error to the client's redirect uri (RFC 6749 section 4.1.2.1).
+    pub fn to_redirect_uri(&self, redirect_uri: &str, state: Option<&str>) -> String {
+        let separator = if redirect_uri.contains('?') { '&' } else { '?' };
+        let mut uri = format!(
+            "{}{}error={}&error_description={}",
+            redirect_uri,
+            separator,
+            self.error.as_str(),
+            RawStr::new(&self.error_description).percent_encode()
+        );
+        if let Some(state) = state {
+            uri.push_str(&format!("&state={}", RawStr::new(state).percent_encode()));
+        }
+        uri
+    }
+}
+
+impl From<Oauth2Error> for OauthErrorResponse {
+    fn from(err: Oauth2Error) -> Self {
+        let error = match &err {
+            Oauth2Error::NoClientWithId | Oauth2Error::InvalidClientSecret => {
+                OauthErrorCode::InvalidClient
+            }
+            Oauth2Error::InvalidClientId
+            | Oauth2Error::InvalidRedirectUri
+            | Oauth2Error::InvalidCode
+            | Oauth2Error::InvalidCodeVerifier
+            | Oauth2Error::RefreshTokenError(
+                RefreshTokenError::InvalidToken | RefreshTokenError::TokenReused,
+            ) => OauthErrorCode::InvalidGrant,
+            Oauth2Error::InvalidScope
+            | Oauth2Error::RefreshTokenError(RefreshTokenError::InvalidScope)
+            | Oauth2Error::TokenExchangeError(TokenExchangeError::InvalidScope) => {
+                OauthErrorCode::InvalidScope
+            }
+            Oauth2Error::TokenExchangeError(TokenExchangeError::InvalidTarget) => {
+                OauthErrorCode::InvalidTarget
+            }
+            Oauth2Error::CodeChallengeRequired
+            | Oauth2Error::InvalidCodeChallenge
+            | Oauth2Error::InvalidConsentRequest
+            | Oauth2Error::InvalidSubjectToken
+            | Oauth2Error::PushedAuthorizationError(PushedAuthorizationError::InvalidRequestUri) => {
+                OauthErrorCode::InvalidRequest
+            }
+            Oauth2Error::ConsentRequired => OauthErrorCode::ConsentRequired,
+            Oauth2Error::DeviceAuthorizationError(
+                DeviceAuthorizationError::AuthorizationPending,
+            ) => OauthErrorCode::AuthorizationPending,
+            Oauth2Error::DeviceAuthorizationError(DeviceAuthorizationError::SlowDown) => {
+                OauthErrorCode::SlowDown
+            }
+            Oauth2Error::DeviceAuthorizationError(DeviceAuthorizationError::AccessDenied) => {
+                OauthErrorCode::AccessDenied
+            }
+            Oauth2Error::DeviceAuthorizationError(DeviceAuthorizationError::ExpiredToken) => {
+                OauthErrorCode::ExpiredToken
+            }
+            _ => {
+                error!("An oauth2 error occurred, err: {}", err);
+                return OauthErrorResponse::server_error();
+            }
+        };
+
+        // The wrapped errors describe what went wrong better than the wrapper.
+        let error_description = match &err {
+            Oauth2Error::RefreshTokenError(err) => err.to_string(),
+            Oauth2Error::DeviceAuthorizationError(err) => err.to_string(),
+            Oauth2Error::PushedAuthorizationError(err) => err.to_string(),
+            Oauth2Error::TokenExchangeError(err) => err.to_string(),
+            err => err.to_string(),
+        };
+
+        OauthErrorResponse {
+            error,
+            error_description,
+        }
+    }
+}
+
+impl From<RegistryAccessError> for OauthErrorResponse {
+    fn from(err: RegistryAccessError) -> Self {
+        match err {
+            RegistryAccessError::InvalidService => {
+                OauthErrorResponse::new(OauthErrorCode::InvalidRequest, &err.to_string())
+            }
+            RegistryAccessError::AccountNotFound => {
+                OauthErrorResponse::new(OauthErrorCode::InvalidGrant, &err.to_string())
+            }
+            RegistryAccessError::RefreshTokenError(
+                err @ (RefreshTokenError::InvalidToken | RefreshTokenError::TokenReused),
+            ) => OauthErrorResponse::new(OauthErrorCode::InvalidGrant, &err.to_string()),
+            err => {
+                error!("Failed to issue registry token, err: {}", err);
+                OauthErrorResponse::server_error()
+            }
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for OauthErrorResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let status = self.error.status();
+        let error = self.error;
+        let mut response = Response::build_from(Json(self).respond_to(request)?);
+        response.status(status);
+        response.raw_header(HEADER_CACHE_CONTROL, NO_STORE);
+        match error {
+            OauthErrorCode::InvalidClient => {
+                response.raw_header(HEADER_WWW_AUTHENTICATE, "Basic");
+            }
+            OauthErrorCode::InvalidToken | OauthErrorCode::InsufficientScope => {
+                response.raw_header(
+                    HEADER_WWW_AUTHENTICATE,
+                    format!("{} error=\"{}\"", TOKEN_TYPE_BEARER, error.as_str()),
+                );
+            }
+            _ => {}
+        }
+        response.ok()
+    }
+}
@@ -0,0 +1,123 @@
+use mobc_redis::RedisConnectionManager;
+use rocket::{form::Form, serde::json::Json, State};
+use serde::Serialize;
+use sqlx::Pool;
+
+use crate::{
+    db::DB,
+    services::{
+        oauth2_authorization_service::{self, Oauth2Error},
+        pushed_authorization_service::AuthorizationParameters,
+    },
+};
+
+use super::{
+    access_token::{client_credentials, AuthHeader},
+    authorize::RESPONSE_TYPE_CODE,
+    oauth_error::{OauthErrorCode, OauthErrorResponse},
+};
+
+#[derive(FromForm, Debug)]
+pub struct PushedAuthorizationRequest {
+    // Either sent in the form (client_secret_post) or in the auth header (client_secret_basic)
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    response_type: String,
+    redirect_uri: String,
+    state: String,
+    scope: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    prompt: Option<String>,
+    max_age: Option<String>,
+    login_hint: Option<String>,
+    nonce: Option<String>,
+    // Not allowed, a pushed request can't refer to another one.
+    request_uri: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PushedAuthorizationSuccessResponse {
+    request_uri: String,
+    expires_in: usize,
+}
+
+#[derive(Responder, Debug)]
+pub enum PushedAuthorizationResponse {
+    #[response(status = 201)]
+    Created(Json<PushedAuthorizationSuccessResponse>),
+    Error(OauthErrorResponse),
+}
+
+/// Pushed authorization request endpoint (RFC 9126 section 2), the client then sends the user to the
+/// authorization endpoint with only its client id and the returned request uri.
+#[post("/par", data = "<request>")]
+pub async fn post_pushed_authorization(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    request: Form<PushedAuthorizationRequest>,
+    auth_header: Option<AuthHeader>,
+) -> PushedAuthorizationResponse {
+    let request = request.into_inner();
+
+    let (client_id, client_secret) =
+        match client_credentials(auth_header, request.client_id, request.client_secret) {
+            Ok(credentials) => credentials,
+            Err(msg) => {
+                return PushedAuthorizationResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    &msg,
+                ))
+            }
+        };
+
+    if request.request_uri.is_some() {
+        return PushedAuthorizationResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::InvalidRequest,
+            "The request_uri parameter can't be pushed",
+        ));
+    }
+
+    if request.response_type != RESPONSE_TYPE_CODE {
+        return PushedAuthorizationResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::UnsupportedResponseType,
+            "Only the code response type is supported",
+        ));
+    }
+
+    let parameters = AuthorizationParameters {
+        response_type: request.response_type,
+        redirect_uri: request.redirect_uri,
+        state: request.state,
+        scope: request.scope,
+        code_challenge: request.code_challenge,
+        code_challenge_method: request.code_challenge_method,
+        prompt: request.prompt,
+        max_age: request.max_age,
+        login_hint: request.login_hint,
+        nonce: request.nonce,
+        reauthentication: None,
+    };
+
+    match oauth2_authorization_service::push_authorization_request(
+        db_pool,
+        redis_pool,
+        client_id,
+        client_secret,
+        parameters,
+    )
+    .await
+    {
+        Ok(pushed) => {
+            PushedAuthorizationResponse::Created(Json(PushedAuthorizationSuccessResponse {
+                request_uri: pushed.request_uri,
+                expires_in: pushed.expires_in,
+            }))
+        }
+        // Unlike at the token endpoint the redirect uri is part of the request itself.
+        Err(err @ Oauth2Error::InvalidRedirectUri) => PushedAuthorizationResponse::Error(
+            OauthErrorResponse::new(OauthErrorCode::InvalidRequest, &err.to_string()),
+        ),
+        Err(err) => PushedAuthorizationResponse::Error(err.into()),
+    }
+}
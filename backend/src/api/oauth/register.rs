@@ -0,0 +1,327 @@
+use openssl::memcmp;
+use rocket::{
+    http::Status,
+    request::{self, FromRequest},
+    response::Responder,
+    serde::json::Json,
+    Request, Response, State,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::Pool;
+
+use crate::{
+    db::DB,
+    models::oauth_client::OauthClient,
+    services::oauth_client_service::{self, ClientMetadata, OauthClientError},
+    util::{config::Config, scope},
+};
+
+use super::{
+    access_token::TOKEN_TYPE_BEARER,
+    oauth_error::{OauthErrorCode, OauthErrorResponse},
+};
+
+const HEADER_CACHE_CONTROL: &str = "Cache-Control";
+const NO_STORE: &str = "no-store";
+
+pub const AUTH_METHOD_CLIENT_SECRET_BASIC: &str = "client_secret_basic";
+pub const AUTH_METHOD_CLIENT_SECRET_POST: &str = "client_secret_post";
+// Public clients, which have no secret and are required to use PKCE
+pub const AUTH_METHOD_NONE: &str = "none";
+
+/// The client metadata accepted by the registration and client configuration endpoints,
+/// unsupported metadata is ignored (RFC 7591 section 2).
+#[derive(Deserialize, Debug)]
+pub struct ClientRegistrationRequest {
+    // Only sent to the client configuration endpoint, where it has to match the registered client.
+    client_id: Option<String>,
+    client_name: Option<String>,
+    #[serde(default)]
+    redirect_uris: Vec<String>,
+    scope: Option<String>,
+    token_endpoint_auth_method: Option<String>,
+    #[serde(default)]
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClientInformationResponse {
+    client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+    client_id_issued_at: i64,
+    // The secrets don't expire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret_expires_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registration_access_token: Option<String>,
+    registration_client_uri: String,
+    client_name: String,
+    redirect_uris: Vec<String>,
+    scope: String,
+    token_endpoint_auth_method: String,
+    post_logout_redirect_uris: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backchannel_logout_uri: Option<String>,
+}
+
+pub enum ClientRegistrationResponse {
+    Created(Json<ClientInformationResponse>),
+    Success(Json<ClientInformationResponse>),
+    Deleted,
+    Error(OauthErrorResponse),
+}
+
+/// Registers a new client (RFC 7591), protected by the initial access token from the config.
+#[post("/register", data = "<request>")]
+pub async fn post_register(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    request: Json<ClientRegistrationRequest>,
+    token: Option<BearerToken>,
+) -> ClientRegistrationResponse {
+    let Some(registration_token) = &config.client_registration_token else {
+        return ClientRegistrationResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::InvalidToken,
+            "Client registration is disabled",
+        ));
+    };
+
+    let is_authorized = token.is_some_and(|token| {
+        token.0.len() == registration_token.len()
+            && memcmp::eq(token.0.as_bytes(), registration_token.as_bytes())
+    });
+    if !is_authorized {
+        return ClientRegistrationResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::InvalidToken,
+            "Invalid initial access token",
+        ));
+    }
+
+    let metadata = match client_metadata(request.into_inner()) {
+        Ok(metadata) => metadata,
+        Err(err) => return ClientRegistrationResponse::Error(err),
+    };
+
+    match oauth_client_service::register_client(db_pool, metadata).await {
+        Ok(registered) => {
+            let mut response = ClientInformationResponse::new(registered.client, config);
+            response.client_secret_expires_at = registered.client_secret.as_ref().map(|_| 0);
+            response.client_secret = registered.client_secret;
+            response.registration_access_token = Some(registered.registration_token);
+            ClientRegistrationResponse::Created(Json(response))
+        }
+        Err(err) => ClientRegistrationResponse::Error(registration_error(err)),
+    }
+}
+
+/// Reads the client's registration (RFC 7592 section 2.1).
+#[get("/register/<client_id>")]
+pub async fn get_registration(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    client_id: String,
+    token: Option<BearerToken>,
+) -> ClientRegistrationResponse {
+    let Some(token) = token else {
+        return ClientRegistrationResponse::Error(missing_token_error());
+    };
+
+    match oauth_client_service::get_registered_client(db_pool, &client_id, &token.0).await {
+        Ok(client) => ClientRegistrationResponse::Success(Json(ClientInformationResponse::new(
+            client, config,
+        ))),
+        Err(err) => ClientRegistrationResponse::Error(registration_error(err)),
+    }
+}
+
+/// Replaces the client's registration (RFC 7592 section 2.2).
+#[put("/register/<client_id>", data = "<request>")]
+pub async fn put_registration(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    client_id: String,
+    request: Json<ClientRegistrationRequest>,
+    token: Option<BearerToken>,
+) -> ClientRegistrationResponse {
+    let Some(token) = token else {
+        return ClientRegistrationResponse::Error(missing_token_error());
+    };
+
+    if request
+        .client_id
+        .as_ref()
+        .is_some_and(|request_client_id| *request_client_id != client_id)
+    {
+        return ClientRegistrationResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::InvalidRequest,
+            "The client ID did not match the registration",
+        ));
+    }
+
+    let metadata = match client_metadata(request.into_inner()) {
+        Ok(metadata) => metadata,
+        Err(err) => return ClientRegistrationResponse::Error(err),
+    };
+
+    match oauth_client_service::update_registered_client(db_pool, &client_id, &token.0, metadata)
+        .await
+    {
+        Ok(client) => ClientRegistrationResponse::Success(Json(ClientInformationResponse::new(
+            client, config,
+        ))),
+        Err(err) => ClientRegistrationResponse::Error(registration_error(err)),
+    }
+}
+
+/// Deletes the client's registration (RFC 7592 section 2.3).
+#[delete("/register/<client_id>")]
+pub async fn delete_registration(
+    db_pool: &State<Pool<DB>>,
+    client_id: String,
+    token: Option<BearerToken>,
+) -> ClientRegistrationResponse {
+    let Some(token) = token else {
+        return ClientRegistrationResponse::Error(missing_token_error());
+    };
+
+    match oauth_client_service::delete_registered_client(db_pool, &client_id, &token.0).await {
+        Ok(()) => ClientRegistrationResponse::Deleted,
+        Err(err) => ClientRegistrationResponse::Error(registration_error(err)),
+    }
+}
+
+impl ClientInformationResponse {
+    fn new(client: OauthClient, config: &Config) -> Self {
+        ClientInformationResponse {
+            registration_client_uri: format!(
+                "{}/api/oauth/register/{}",
+                config.backend_address, client.client_id
+            ),
+            client_id: client.client_id,
+            client_secret: None,
+            client_id_issued_at: client.created_at.timestamp(),
+            client_secret_expires_at: None,
+            registration_access_token: None,
+            client_name: client.client_name,
+            redirect_uris: client.redirect_uris,
+            scope: scope::format_scopes(&client.allowed_scopes),
+            token_endpoint_auth_method: if client.public_client {
+                AUTH_METHOD_NONE.to_string()
+            } else {
+                AUTH_METHOD_CLIENT_SECRET_BASIC.to_string()
+            },
+            post_logout_redirect_uris: client.post_logout_redirect_uris,
+            backchannel_logout_uri: client.backchannel_logout_uri,
+        }
+    }
+}
+
+fn client_metadata(
+    request: ClientRegistrationRequest,
+) -> Result<ClientMetadata, OauthErrorResponse> {
+    let Some(client_name) = request.client_name else {
+        return Err(OauthErrorResponse::new(
+            OauthErrorCode::InvalidClientMetadata,
+            "Missing client name",
+        ));
+    };
+
+    let public_client = match request.token_endpoint_auth_method.as_deref() {
+        None | Some(AUTH_METHOD_CLIENT_SECRET_BASIC) | Some(AUTH_METHOD_CLIENT_SECRET_POST) => {
+            false
+        }
+        Some(AUTH_METHOD_NONE) => true,
+        Some(_) => {
+            return Err(OauthErrorResponse::new(
+                OauthErrorCode::InvalidClientMetadata,
+                "Unsupported token endpoint auth method",
+            ))
+        }
+    };
+
+    Ok(ClientMetadata {
+        client_name,
+        redirect_uris: request.redirect_uris,
+        scopes: request.scope.as_deref().map(scope::parse_scopes),
+        public_client,
+        post_logout_redirect_uris: request.post_logout_redirect_uris,
+        backchannel_logout_uri: request.backchannel_logout_uri,
+    })
+}
+
+fn missing_token_error() -> OauthErrorResponse {
+    OauthErrorResponse::new(
+        OauthErrorCode::InvalidToken,
+        "Missing registration access token",
+    )
+}
+
+fn registration_error(err: OauthClientError) -> OauthErrorResponse {
+    match err {
+        OauthClientError::InvalidRedirectUri => {
+            OauthErrorResponse::new(OauthErrorCode::InvalidRedirectUri, &err.to_string())
+        }
+        OauthClientError::ClientNameTaken
+        | OauthClientError::InvalidScope
+        | OauthClientError::InvalidBackchannelLogoutUri
+        | OauthClientError::AuthMethodChanged => {
+            OauthErrorResponse::new(OauthErrorCode::InvalidClientMetadata, &err.to_string())
+        }
+        // Don't reveal whether the client exists to callers without a valid token.
+        OauthClientError::InvalidRegistrationToken | OauthClientError::ClientIdNotFound => {
+            OauthErrorResponse::new(
+                OauthErrorCode::InvalidToken,
+                &OauthClientError::InvalidRegistrationToken.to_string(),
+            )
+        }
+        err => {
+            error!("Failed to manage client registration, err: {}", err);
+            OauthErrorResponse::server_error()
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ClientRegistrationResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let (status, content) = match self {
+            ClientRegistrationResponse::Created(content) => (Status::Created, content),
+            ClientRegistrationResponse::Success(content) => (Status::Ok, content),
+            ClientRegistrationResponse::Deleted => {
+                return Response::build().status(Status::NoContent).ok()
+            }
+            ClientRegistrationResponse::Error(err) => return err.respond_to(request),
+        };
+
+        let mut response = Response::build_from(content.respond_to(request)?);
+        response.status(status);
+        response.raw_header(HEADER_CACHE_CONTROL, NO_STORE);
+        response.ok()
+    }
+}
+
+/// A bearer token from the Authorization header, either the initial access token
+/// or a client's registration access token.
+pub struct BearerToken(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let Some(token) = req
+            .headers()
+            .get_one("authorization")
+            .and_then(|header| header.strip_prefix(&format!("{TOKEN_TYPE_BEARER} ")))
+        else {
+            error!("Got registration request without a bearer token");
+            return request::Outcome::Error((
+                Status::Unauthorized,
+                "Missing bearer token".to_string(),
+            ));
+        };
+
+        request::Outcome::Success(BearerToken(token.to_string()))
+    }
+}
@@ -0,0 +1,63 @@
+use mobc_redis::RedisConnectionManager;
+use rocket::{form::Form, State};
+use sqlx::Pool;
+
+use crate::{db::DB, services::oauth2_authorization_service};
+
+use super::{
+    access_token::{client_credentials, AuthHeader},
+    oauth_error::{OauthErrorCode, OauthErrorResponse},
+};
+
+#[derive(FromForm, Debug)]
+pub struct RevokeRequest {
+    token: String,
+    token_type_hint: Option<String>,
+    // Either sent in the form (client_secret_post) or in the auth header (client_secret_basic)
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Responder, Debug)]
+pub enum RevokeResponse {
+    #[response(status = 200)]
+    Success(()),
+    Error(OauthErrorResponse),
+}
+
+/// Revokes an access or refresh token (RFC 7009).
+/// Responds with success even if the token is invalid, as there is nothing left for the client to do.
+#[post("/revoke", data = "<request>")]
+pub async fn post_revoke(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    request: Form<RevokeRequest>,
+    auth_header: Option<AuthHeader>,
+) -> RevokeResponse {
+    let request = request.into_inner();
+
+    let (client_id, client_secret) =
+        match client_credentials(auth_header, request.client_id, request.client_secret) {
+            Ok(credentials) => credentials,
+            Err(msg) => {
+                return RevokeResponse::Error(OauthErrorResponse::new(
+                    OauthErrorCode::InvalidRequest,
+                    &msg,
+                ))
+            }
+        };
+
+    match oauth2_authorization_service::revoke_token(
+        db_pool,
+        redis_pool,
+        client_id,
+        client_secret,
+        request.token,
+        request.token_type_hint,
+    )
+    .await
+    {
+        Ok(()) => RevokeResponse::Success(()),
+        Err(err) => RevokeResponse::Error(err.into()),
+    }
+}
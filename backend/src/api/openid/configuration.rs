@@ -1,42 +1,165 @@
+use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::State;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
+use sqlx::Pool;
 
+use crate::api::oauth::access_token::{
+    GRANT_TYPE_AUTHORIZATION_CODE, GRANT_TYPE_CLIENT_CREDENTIALS, GRANT_TYPE_DEVICE_CODE,
+    GRANT_TYPE_REFRESH_TOKEN, GRANT_TYPE_TOKEN_EXCHANGE,
+};
+use crate::api::oauth::register::{
+    AUTH_METHOD_CLIENT_SECRET_BASIC, AUTH_METHOD_CLIENT_SECRET_POST, AUTH_METHOD_NONE,
+};
+use crate::db::DB;
+use crate::services::{oauth_client_service, signing_key_service};
 use crate::util::config::Config;
+use crate::util::pkce::{CODE_CHALLENGE_METHOD_PLAIN, CODE_CHALLENGE_METHOD_S256};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct OpenIdConfiguration {
+/// The metadata of the authorization server (RFC 8414), which is a superset of the
+/// OpenID Provider metadata so both discovery documents share it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerMetadata {
     issuer: String,
     authorization_endpoint: String,
     token_endpoint: String,
+    token_endpoint_auth_methods_supported: Vec<String>,
     userinfo_endpoint: String,
     jwks_uri: String,
+    revocation_endpoint: String,
+    revocation_endpoint_auth_methods_supported: Vec<String>,
+    introspection_endpoint: String,
+    introspection_endpoint_auth_methods_supported: Vec<String>,
+    device_authorization_endpoint: String,
+    pushed_authorization_request_endpoint: String,
+    // Whether every client has to use pushed authorization requests, otherwise it can still be required per client
+    require_pushed_authorization_requests: bool,
+    end_session_endpoint: String,
+    // Only advertised when an initial access token has been configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registration_endpoint: Option<String>,
+    backchannel_logout_supported: bool,
+    backchannel_logout_session_supported: bool,
     scopes_supported: Vec<String>,
     response_types_supported: Vec<String>,
     grant_types_supported: Vec<String>,
     subject_types_supported: Vec<String>,
     id_token_signing_alg_values_supported: Vec<String>,
     claims_supported: Vec<String>,
+    code_challenge_methods_supported: Vec<String>,
 }
 
 #[derive(Responder, Debug)]
-pub enum OpenIdConfigurationResponse {
-    Success(Json<OpenIdConfiguration>),
+pub enum ServerMetadataResponse {
+    Success(Json<ServerMetadata>),
 }
 
+/// OpenID Connect Discovery, served both at the issuer root and under the OpenID API.
 #[get("/.well-known/openid-configuration")]
-pub async fn get_openid_configuration(config: &State<Config>) -> OpenIdConfigurationResponse {
-    OpenIdConfigurationResponse::Success(Json(OpenIdConfiguration {
+pub async fn get_openid_configuration(
+    config: &State<Config>,
+    db_pool: &State<Pool<DB>>,
+) -> Result<ServerMetadataResponse, Status> {
+    let metadata = server_metadata(config, db_pool).await?;
+    Ok(ServerMetadataResponse::Success(Json(metadata)))
+}
+
+/// OAuth 2.0 Authorization Server Metadata (RFC 8414), for clients that don't speak OpenID Connect.
+#[get("/.well-known/oauth-authorization-server")]
+pub async fn get_authorization_server_metadata(
+    config: &State<Config>,
+    db_pool: &State<Pool<DB>>,
+) -> Result<ServerMetadataResponse, Status> {
+    let metadata = server_metadata(config, db_pool).await?;
+    Ok(ServerMetadataResponse::Success(Json(metadata)))
+}
+
+async fn server_metadata(config: &Config, db_pool: &Pool<DB>) -> Result<ServerMetadata, Status> {
+    let mut signing_algorithms = match signing_key_service::get_published_keys(db_pool).await {
+        Ok(keys) => keys.into_iter().map(|key| key.alg).collect::<Vec<String>>(),
+        Err(err) => {
+            error!("Failed to retrieve signing keys, err: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+    signing_algorithms.sort();
+    signing_algorithms.dedup();
+
+    // Includes the custom scopes clients have been given, not only the standard ones.
+    let scopes_supported = match oauth_client_service::get_supported_scopes(db_pool).await {
+        Ok(scopes) => scopes,
+        Err(err) => {
+            error!("Failed to retrieve supported scopes, err: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    Ok(ServerMetadata {
         issuer: config.backend_address.clone(),
         authorization_endpoint: format!("{}/api/oauth/authorize", config.backend_address),
         token_endpoint: format!("{}/api/oauth/token", config.backend_address),
+        token_endpoint_auth_methods_supported: vec![
+            AUTH_METHOD_CLIENT_SECRET_BASIC.to_string(),
+            AUTH_METHOD_CLIENT_SECRET_POST.to_string(),
+            AUTH_METHOD_NONE.to_string(),
+        ],
         userinfo_endpoint: format!("{}/api/openid/userinfo", config.backend_address),
-        jwks_uri: Default::default(), // TODO: Not implemented
-        scopes_supported: vec![], // TODO: We should probably support scopes, at least openid (as it is required by the spec).
+        jwks_uri: format!("{}/api/openid/jwks.json", config.backend_address),
+        revocation_endpoint: format!("{}/api/oauth/revoke", config.backend_address),
+        revocation_endpoint_auth_methods_supported: vec![
+            AUTH_METHOD_CLIENT_SECRET_BASIC.to_string(),
+            AUTH_METHOD_CLIENT_SECRET_POST.to_string(),
+            AUTH_METHOD_NONE.to_string(),
+        ],
+        introspection_endpoint: format!("{}/api/oauth/introspect", config.backend_address),
+        // Only confidential clients may introspect tokens
+        introspection_endpoint_auth_methods_supported: vec![
+            AUTH_METHOD_CLIENT_SECRET_BASIC.to_string(),
+            AUTH_METHOD_CLIENT_SECRET_POST.to_string(),
+        ],
+        device_authorization_endpoint: format!(
+            "{}/api/oauth/device_authorization",
+            config.backend_address
+        ),
+        pushed_authorization_request_endpoint: format!("{}/api/oauth/par", config.backend_address),
+        require_pushed_authorization_requests: config.require_pushed_authorization_requests,
+        end_session_endpoint: format!("{}/api/openid/end_session", config.backend_address),
+        registration_endpoint: config
+            .client_registration_token
+            .as_ref()
+            .map(|_| format!("{}/api/oauth/register", config.backend_address)),
+        backchannel_logout_supported: true,
+        backchannel_logout_session_supported: true,
+        scopes_supported,
         response_types_supported: vec!["code".to_string()],
-        grant_types_supported: vec!["authorization_code".to_string()],
+        // The password grant is left out, it's only accepted for docker registry tokens
+        grant_types_supported: vec![
+            GRANT_TYPE_AUTHORIZATION_CODE.to_string(),
+            GRANT_TYPE_REFRESH_TOKEN.to_string(),
+            GRANT_TYPE_CLIENT_CREDENTIALS.to_string(),
+            GRANT_TYPE_DEVICE_CODE.to_string(),
+            GRANT_TYPE_TOKEN_EXCHANGE.to_string(),
+        ],
         subject_types_supported: vec!["public".to_string()],
-        id_token_signing_alg_values_supported: vec![], // TODO: Not implemented
-        claims_supported: vec!["email".to_string()],   // TODO: Support aud, exp, iss, iat, sub etc.
-    }))
+        id_token_signing_alg_values_supported: signing_algorithms,
+        claims_supported: vec![
+            "iss".to_string(),
+            "sub".to_string(),
+            "aud".to_string(),
+            "exp".to_string(),
+            "iat".to_string(),
+            "auth_time".to_string(),
+            "nonce".to_string(),
+            "sid".to_string(),
+            "name".to_string(),
+            "given_name".to_string(),
+            "family_name".to_string(),
+            "email".to_string(),
+            "email_verified".to_string(),
+        ],
+        code_challenge_methods_supported: vec![
+            CODE_CHALLENGE_METHOD_S256.to_string(),
+            CODE_CHALLENGE_METHOD_PLAIN.to_string(),
+        ],
+    })
 }
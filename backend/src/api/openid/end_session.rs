@@ -0,0 +1,187 @@
+use mobc_redis::RedisConnectionManager;
+use rocket::{form::Form, http::CookieJar, http::Status, response::Redirect, Either, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
+use sqlx::Pool;
+
+use crate::{
+    api::{
+        auth::session_guard::{delete_session, delete_session_cookie, Session},
+        response::{ErrMsg, ResponseStatus},
+    },
+    db::DB,
+    services::{
+        backchannel_logout_service,
+        end_session_service::{self, EndSessionError},
+        signing_key_service::SigningKeyCache,
+    },
+    util::config::Config,
+};
+
+const LOGGED_OUT_ADDRESS: &str = "/";
+
+const END_SESSION_TEMPLATE_NAME: &str = "end-session";
+
+#[derive(FromForm, Debug)]
+pub struct EndSessionRequest {
+    id_token_hint: Option<String>,
+    client_id: Option<String>,
+    post_logout_redirect_uri: Option<String>,
+    state: Option<String>,
+    // The user's answer on the confirmation page, only accepted in a POST.
+    logout: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+struct EndSessionTemplateData {
+    client_id: Option<String>,
+    post_logout_redirect_uri: Option<String>,
+    state: Option<String>,
+}
+
+/// RP-initiated logout, relying parties may send the user here either with a GET or a form POST.
+#[get("/end_session?<request..>")]
+pub async fn get_end_session(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
+    request: EndSessionRequest,
+    session: Option<Session>,
+    cookies: &CookieJar<'_>,
+) -> Result<Either<Template, Redirect>, ResponseStatus<()>> {
+    end_session(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        request,
+        None,
+        session,
+        cookies,
+    )
+    .await
+}
+
+#[post("/end_session", data = "<request>")]
+pub async fn post_end_session(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
+    request: Form<EndSessionRequest>,
+    session: Option<Session>,
+    cookies: &CookieJar<'_>,
+) -> Result<Either<Template, Redirect>, ResponseStatus<()>> {
+    let request = request.into_inner();
+    let logout = request.logout;
+    end_session(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        request,
+        logout,
+        session,
+        cookies,
+    )
+    .await
+}
+
+/// The user is only logged out without being asked if the ID token hint shows that the request
+/// comes from a client they are logged in to, otherwise any site could log them out.
+#[allow(clippy::too_many_arguments)]
+async fn end_session(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &State<SigningKeyCache>,
+    request: EndSessionRequest,
+    logout: Option<bool>,
+    session: Option<Session>,
+    cookies: &CookieJar<'_>,
+) -> Result<Either<Template, Redirect>, ResponseStatus<()>> {
+    let confirmation = EndSessionTemplateData {
+        client_id: request.client_id.clone(),
+        post_logout_redirect_uri: request.post_logout_redirect_uri.clone(),
+        state: request.state.clone(),
+    };
+
+    let end_session = match end_session_service::end_session(
+        db_pool,
+        config,
+        signing_keys,
+        request.id_token_hint.as_deref(),
+        request.client_id,
+        request.post_logout_redirect_uri,
+        request.state.as_deref(),
+    )
+    .await
+    {
+        Ok(end_session) => end_session,
+        Err(EndSessionError::InvalidIdTokenHint | EndSessionError::ClientIdMismatch) => {
+            return Err(ResponseStatus::err(
+                Status::BadRequest,
+                ErrMsg::InvalidIdTokenHint,
+            ))
+        }
+        Err(EndSessionError::NoClientWithId) => {
+            return Err(ResponseStatus::err(
+                Status::BadRequest,
+                ErrMsg::InvalidClientId,
+            ))
+        }
+        Err(EndSessionError::InvalidPostLogoutRedirectUri) => {
+            return Err(ResponseStatus::err(
+                Status::BadRequest,
+                ErrMsg::InvalidRedirectUri,
+            ))
+        }
+        Err(err) => {
+            error!("Failed to end session, err: {}", err);
+            return Err(ResponseStatus::internal_err());
+        }
+    };
+
+    // A hint for another account means that the user has already logged out of that account.
+    let session = session.filter(|session| {
+        end_session
+            .account_id
+            .iter()
+            .all(|account_id| *account_id == session.account_id)
+    });
+
+    // The answer can't be forged by another site as the lax session cookie isn't sent with
+    // cross-site POSTs.
+    if session.is_some() && end_session.account_id.is_none() && logout.is_none() {
+        return Ok(Either::Left(Template::render(
+            END_SESSION_TEMPLATE_NAME,
+            confirmation,
+        )));
+    }
+
+    if let Some(session) = session.filter(|_| logout != Some(false)) {
+        if let Err(err) = delete_session(redis_pool, &session.id).await {
+            error!("Failed to delete session from redis DB, err: {}", err);
+        }
+        delete_session_cookie(cookies).await;
+
+        if let Err(err) = backchannel_logout_service::logout_session(
+            db_pool,
+            redis_pool,
+            config,
+            signing_keys,
+            &session.id,
+            session.account_id,
+        )
+        .await
+        {
+            error!("Failed to send backchannel logouts, err: {}", err);
+        }
+    }
+
+    let redirect_uri = end_session
+        .redirect_uri
+        .unwrap_or_else(|| LOGGED_OUT_ADDRESS.to_string());
+    Ok(Either::Right(Redirect::found(redirect_uri)))
+}
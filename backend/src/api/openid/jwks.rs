@@ -0,0 +1,41 @@
+use rocket::{http::Header, serde::json::Json, State};
+use serde::Serialize;
+use sqlx::Pool;
+
+use crate::{
+    db::DB,
+    services::signing_key_service::{self, Jwk, JWKS_MAX_AGE_SECONDS},
+};
+
+const HEADER_CACHE_CONTROL: &str = "Cache-Control";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Responder, Debug)]
+pub enum JwksResponse {
+    #[response(status = 200)]
+    Success(Json<Jwks>, Header<'static>),
+    #[response(status = 500)]
+    Failure(String),
+}
+
+#[get("/jwks.json")]
+pub async fn get_jwks(db_pool: &State<Pool<DB>>) -> JwksResponse {
+    match signing_key_service::get_published_keys(db_pool).await {
+        // New keys are published at least this long before they are used, so caching them is safe.
+        Ok(keys) => JwksResponse::Success(
+            Json(Jwks { keys }),
+            Header::new(
+                HEADER_CACHE_CONTROL,
+                format!("public, max-age={JWKS_MAX_AGE_SECONDS}"),
+            ),
+        ),
+        Err(err) => {
+            error!("Failed to retrieve signing keys, err: {}", err);
+            JwksResponse::Failure("An unknown error occurred".to_string())
+        }
+    }
+}
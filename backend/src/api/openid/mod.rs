@@ -1,11 +1,24 @@
 use rocket::Route;
 
 pub mod configuration;
+pub mod end_session;
+pub mod jwks;
 pub mod userinfo;
 
 pub fn openid_routes() -> Vec<Route> {
     routes![
         userinfo::get_userinfo,
-        configuration::get_openid_configuration
+        configuration::get_openid_configuration,
+        jwks::get_jwks,
+        end_session::get_end_session,
+        end_session::post_end_session,
+    ]
+}
+
+/// Discovery documents served at the issuer root, where generic clients look for them.
+pub fn well_known_routes() -> Vec<Route> {
+    routes![
+        configuration::get_openid_configuration,
+        configuration::get_authorization_server_metadata,
     ]
 }
@@ -3,14 +3,30 @@ use serde::Serialize;
 use sqlx::Pool;
 
 use crate::{
-    api::auth::access_token_guard::AccessTokenAuth,
+    api::{
+        auth::access_token_guard::AccessTokenAuth,
+        oauth::oauth_error::{OauthErrorCode, OauthErrorResponse},
+    },
     db::DB,
     services::user_info_service::{self, UserInfoError},
+    util::scope::{self, SCOPE_EMAIL, SCOPE_OPENID, SCOPE_PROFILE},
 };
 
+/// The standard claims (OpenID Connect Core section 5.1), `sub` is always included
+/// while the others depend on the scopes granted to the token.
 #[derive(Debug, Clone, Serialize)]
 pub struct Userinfo {
-    email: String,
+    sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    family_name: Option<String>,
 }
 
 #[derive(Responder, Debug)]
@@ -19,6 +35,7 @@ pub enum UserinfoResponse {
     Success(Json<Userinfo>),
     #[response(status = 401)]
     Unauthorized(String),
+    Error(OauthErrorResponse),
     #[response(status = 500)]
     Failure(String),
 }
@@ -28,6 +45,15 @@ pub async fn get_userinfo(
     access_token: AccessTokenAuth,
     db_pool: &State<Pool<DB>>,
 ) -> UserinfoResponse {
+    let scopes = access_token.access_token.scopes.clone();
+    // The userinfo endpoint is only for tokens from OpenID Connect requests (OpenID Connect Core section 5.3).
+    if !scope::has_scope(&scopes, SCOPE_OPENID) {
+        return UserinfoResponse::Error(OauthErrorResponse::new(
+            OauthErrorCode::InsufficientScope,
+            "The access token wasn't granted the openid scope",
+        ));
+    }
+
     let user_info = match user_info_service::get_user_info(db_pool, access_token.access_token).await
     {
         Ok(user_info) => user_info,
@@ -40,7 +66,16 @@ pub async fn get_userinfo(
         }
     };
 
+    let include_email = scope::has_scope(&scopes, SCOPE_EMAIL);
+    let include_profile = scope::has_scope(&scopes, SCOPE_PROFILE);
+
+    let account = user_info.account;
     UserinfoResponse::Success(Json(Userinfo {
-        email: user_info.email,
+        sub: account.id.to_string(),
+        email: include_email.then_some(user_info.email),
+        email_verified: include_email.then_some(user_info.email_verified),
+        name: include_profile.then(|| format!("{} {}", account.first_name, account.last_name)),
+        given_name: include_profile.then_some(account.first_name),
+        family_name: include_profile.then_some(account.last_name),
     }))
 }
@@ -74,9 +74,19 @@ pub enum ErrMsg {
     InvalidUuid,
     OauthClientNameTaken,
     InvalidClientId,
-    InvalidResponseType,
+    InvalidClientSecretId,
+    InvalidAccountId,
+    InvalidRegistryAccessRule,
+    InvalidRegistryAccessRuleId,
+    InvalidTokenExchangePolicy,
+    TokenExchangePolicyExists,
+    InvalidTokenExchangePolicyId,
     InvalidRedirectUri,
+    InvalidConsentRequest,
+    InvalidRequestUri,
+    InvalidIdTokenHint,
     InvalidAccessToken,
+    ClientAccessToken,
 }
 
 impl Display for ErrMsg {
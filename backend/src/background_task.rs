@@ -1,8 +1,13 @@
 use crate::db::{
     account_repository, activation_code_repository, login_details_repository, new_transaction,
-    reset_password_repository, DB,
+    oauth_client_secret_repository, refresh_token_repository, reset_password_repository, DB,
+};
+use crate::services::{
+    backchannel_logout_service,
+    signing_key_service::{self, SigningKeyCache},
 };
 use crate::util::accounts_error::AccountsResult;
+use crate::util::config::Config;
 use sqlx::types::uuid::Uuid;
 use sqlx::Pool;
 use std::time::Duration;
@@ -11,7 +16,11 @@ use tokio::time;
 // 30 minutes
 const SECONDS_BETWEEN_TASKS: u64 = 60 * 30;
 
-pub async fn run_background_tasks(db_pool: Pool<DB>) {
+pub async fn run_background_tasks(
+    db_pool: Pool<DB>,
+    config: Config,
+    signing_keys: SigningKeyCache,
+) {
     let time_between_tasks = Duration::from_secs(SECONDS_BETWEEN_TASKS);
 
     // Keep running the jobs forever
@@ -22,6 +31,21 @@ pub async fn run_background_tasks(db_pool: Pool<DB>) {
         delete_unused_password_resets(&db_pool)
             .await
             .expect("Failed to delete unused password resets");
+        delete_expired_refresh_tokens(&db_pool)
+            .await
+            .expect("Failed to delete expired refresh tokens");
+        delete_expired_client_secrets(&db_pool)
+            .await
+            .expect("Failed to delete expired client secrets");
+        signing_key_service::rotate_signing_keys(&db_pool, &config, &signing_keys)
+            .await
+            .expect("Failed to rotate signing keys");
+        // Unreachable clients shouldn't stop the other tasks.
+        if let Err(err) =
+            backchannel_logout_service::retry_failed_logouts(&db_pool, &config, &signing_keys).await
+        {
+            println!("Failed to retry backchannel logouts, err: {err}");
+        }
         println!(
             "Run successful, sleeping for {} minutes",
             time_between_tasks.as_secs() / 60
@@ -91,3 +115,37 @@ async fn delete_unused_password_resets(db_pool: &Pool<DB>) -> AccountsResult<()>
 
     Ok(())
 }
+
+async fn delete_expired_refresh_tokens(db_pool: &Pool<DB>) -> AccountsResult<()> {
+    println!("Begin deletion of expired refresh tokens");
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let expired_refresh_tokens = refresh_token_repository::delete_expired(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    println!(
+        "Delete expired refresh tokens job ran successfully, deleted {}",
+        expired_refresh_tokens.len()
+    );
+
+    Ok(())
+}
+
+async fn delete_expired_client_secrets(db_pool: &Pool<DB>) -> AccountsResult<()> {
+    println!("Begin deletion of expired client secrets");
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let expired_secrets = oauth_client_secret_repository::delete_expired(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    println!(
+        "Delete expired client secrets job ran successfully, deleted {}",
+        expired_secrets.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{models::backchannel_logout::BackchannelLogout, util::accounts_error::AccountsResult};
+
+use super::DB;
+
+pub async fn insert(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: String,
+    account_id: Uuid,
+    sid: Option<String>,
+    next_attempt_at: DateTime<Utc>,
+) -> AccountsResult<BackchannelLogout> {
+    Ok(sqlx::query_as!(
+        BackchannelLogout,
+        "
+INSERT INTO backchannel_logout (client_id, account_id, sid, next_attempt_at)
+VALUES                         ($1,        $2,         $3,  $4)
+RETURNING *
+        ",
+        client_id,
+        account_id,
+        sid,
+        next_attempt_at
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_due(
+    transaction: &mut Transaction<'_, DB>,
+) -> AccountsResult<Vec<BackchannelLogout>> {
+    Ok(sqlx::query_as!(
+        BackchannelLogout,
+        "
+SELECT *
+FROM backchannel_logout
+WHERE next_attempt_at <= NOW()
+        "
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+/// Records a failed delivery attempt.
+pub async fn reschedule(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    next_attempt_at: DateTime<Utc>,
+) -> AccountsResult<()> {
+    sqlx::query_as!(
+        BackchannelLogout,
+        "
+UPDATE backchannel_logout
+SET attempts = attempts + 1,
+    next_attempt_at = $2,
+    modified_at = NOW()
+WHERE id = $1
+        ",
+        id,
+        next_attempt_at
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete(transaction: &mut Transaction<'_, DB>, id: Uuid) -> AccountsResult<()> {
+    sqlx::query_as!(
+        BackchannelLogout,
+        "
+DELETE
+FROM backchannel_logout
+WHERE id = $1
+        ",
+        id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
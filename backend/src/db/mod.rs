@@ -3,11 +3,18 @@ use sqlx::{Pool, Postgres, Transaction};
 
 pub mod account_repository;
 pub mod activation_code_repository;
+pub mod backchannel_logout_repository;
 pub mod login_details_repository;
 pub mod login_provider_repository;
 pub mod oauth_client_repository;
+pub mod oauth_client_secret_repository;
+pub mod oauth_consent_repository;
+pub mod refresh_token_repository;
+pub mod registry_access_rule_repository;
 pub mod reset_password_repository;
+pub mod signing_key_repository;
 pub mod third_party_login_repository;
+pub mod token_exchange_policy_repository;
 pub mod whitelist_repository;
 
 use crate::models::login_provider::LOCAL_LOGIN_PROVIDER;
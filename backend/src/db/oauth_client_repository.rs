@@ -4,24 +4,35 @@ use crate::{models::oauth_client::OauthClient, util::accounts_error::AccountsRes
 
 use super::DB;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn insert(
     transaction: &mut Transaction<'_, DB>,
     client_id: String,
-    client_secret: String,
     client_name: String,
-    redirect_uri: String,
+    redirect_uris: Vec<String>,
+    allowed_scopes: Vec<String>,
+    require_pkce: bool,
+    public_client: bool,
+    first_party: bool,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
 ) -> AccountsResult<OauthClient> {
     Ok(sqlx::query_as!(
         OauthClient,
         "
-INSERT INTO oauth_client (client_id, client_secret, client_name, redirect_uri)
-VALUES                   ($1,        $2,            $3,          $4)
+INSERT INTO oauth_client (client_id, client_name, redirect_uris, allowed_scopes, require_pkce, public_client, first_party, post_logout_redirect_uris, backchannel_logout_uri)
+VALUES                   ($1,        $2,          $3,            $4,             $5,           $6,            $7,          $8,                        $9)
 RETURNING *
         ",
         client_id,
-        client_secret,
         client_name,
-        redirect_uri
+        &redirect_uris,
+        &allowed_scopes,
+        require_pkce,
+        public_client,
+        first_party,
+        &post_logout_redirect_uris,
+        backchannel_logout_uri
     )
     .fetch_one(&mut **transaction)
     .await?)
@@ -90,3 +101,202 @@ WHERE client_id=$1
     .fetch_optional(&mut **transaction)
     .await?)
 }
+
+pub async fn update_redirect_uris(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    redirect_uris: Vec<String>,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET redirect_uris = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        &redirect_uris
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn update_post_logout_redirect_uris(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    post_logout_redirect_uris: Vec<String>,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET post_logout_redirect_uris = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        &post_logout_redirect_uris
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn update_backchannel_logout_uri(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    backchannel_logout_uri: Option<String>,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET backchannel_logout_uri = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        backchannel_logout_uri
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn update_registration_token_hash(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    registration_token_hash: String,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET registration_token_hash = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        registration_token_hash
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+/// Replaces the metadata a client controls through the client configuration endpoint.
+#[allow(clippy::too_many_arguments)]
+pub async fn update_metadata(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    client_name: String,
+    redirect_uris: Vec<String>,
+    allowed_scopes: Vec<String>,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET client_name = $2,
+    redirect_uris = $3,
+    allowed_scopes = $4,
+    post_logout_redirect_uris = $5,
+    backchannel_logout_uri = $6,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        client_name,
+        &redirect_uris,
+        &allowed_scopes,
+        &post_logout_redirect_uris,
+        backchannel_logout_uri
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn update_jwt_access_tokens(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    jwt_access_tokens: bool,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET jwt_access_tokens = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        jwt_access_tokens
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn update_require_pushed_authorization_requests(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    require_pushed_authorization_requests: bool,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET require_pushed_authorization_requests = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        require_pushed_authorization_requests
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn update_first_party(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+    first_party: bool,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+UPDATE oauth_client
+SET first_party = $2,
+    modified_at = NOW()
+WHERE id = $1
+RETURNING *
+        ",
+        id,
+        first_party
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_by_id(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+) -> AccountsResult<Option<OauthClient>> {
+    Ok(sqlx::query_as!(
+        OauthClient,
+        "
+SELECT *
+FROM oauth_client
+WHERE id = $1
+        ",
+        id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{models::oauth_client_secret::OauthClientSecret, util::accounts_error::AccountsResult};
+
+use super::DB;
+
+pub async fn insert(
+    transaction: &mut Transaction<'_, DB>,
+    oauth_client_id: Uuid,
+    secret_hash: String,
+) -> AccountsResult<OauthClientSecret> {
+    Ok(sqlx::query_as!(
+        OauthClientSecret,
+        "
+INSERT INTO oauth_client_secret (oauth_client_id, secret_hash)
+VALUES                          ($1,              $2)
+RETURNING *
+        ",
+        oauth_client_id,
+        secret_hash
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_by_client(
+    transaction: &mut Transaction<'_, DB>,
+    oauth_client_id: Uuid,
+) -> AccountsResult<Vec<OauthClientSecret>> {
+    Ok(sqlx::query_as!(
+        OauthClientSecret,
+        "
+SELECT *
+FROM oauth_client_secret
+WHERE oauth_client_id = $1
+ORDER BY created_at
+        ",
+        oauth_client_id
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_active_by_client(
+    transaction: &mut Transaction<'_, DB>,
+    oauth_client_id: Uuid,
+) -> AccountsResult<Vec<OauthClientSecret>> {
+    Ok(sqlx::query_as!(
+        OauthClientSecret,
+        "
+SELECT *
+FROM oauth_client_secret
+WHERE oauth_client_id = $1 AND (expires_at IS NULL OR expires_at > NOW())
+        ",
+        oauth_client_id
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+/// Sets the expiration of all of the client's secrets that would otherwise expire later.
+pub async fn expire_all_by_client(
+    transaction: &mut Transaction<'_, DB>,
+    oauth_client_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> AccountsResult<Vec<OauthClientSecret>> {
+    Ok(sqlx::query_as!(
+        OauthClientSecret,
+        "
+UPDATE oauth_client_secret
+SET expires_at = $2,
+    modified_at = NOW()
+WHERE oauth_client_id = $1 AND (expires_at IS NULL OR expires_at > $2)
+RETURNING *
+        ",
+        oauth_client_id,
+        expires_at
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+pub async fn delete(
+    transaction: &mut Transaction<'_, DB>,
+    oauth_client_id: Uuid,
+    id: Uuid,
+) -> AccountsResult<Option<OauthClientSecret>> {
+    Ok(sqlx::query_as!(
+        OauthClientSecret,
+        "
+DELETE
+FROM oauth_client_secret
+WHERE oauth_client_id = $1 AND id = $2
+RETURNING *
+        ",
+        oauth_client_id,
+        id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn delete_expired(
+    transaction: &mut Transaction<'_, DB>,
+) -> AccountsResult<Vec<OauthClientSecret>> {
+    Ok(sqlx::query_as!(
+        OauthClientSecret,
+        "
+DELETE
+FROM oauth_client_secret
+WHERE expires_at < NOW()
+RETURNING *
+        "
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
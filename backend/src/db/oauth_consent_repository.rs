@@ -0,0 +1,49 @@
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{models::oauth_consent::OauthConsent, util::accounts_error::AccountsResult};
+
+use super::DB;
+
+pub async fn get_by_account_and_client(
+    transaction: &mut Transaction<'_, DB>,
+    account_id: Uuid,
+    client_id: &str,
+) -> AccountsResult<Option<OauthConsent>> {
+    Ok(sqlx::query_as!(
+        OauthConsent,
+        "
+SELECT *
+FROM oauth_consent
+WHERE account_id = $1 AND client_id = $2
+        ",
+        account_id,
+        client_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+/// Stores the scopes the account has consented to, replacing any previous consent for the client.
+pub async fn upsert(
+    transaction: &mut Transaction<'_, DB>,
+    account_id: Uuid,
+    client_id: String,
+    scopes: Vec<String>,
+) -> AccountsResult<OauthConsent> {
+    Ok(sqlx::query_as!(
+        OauthConsent,
+        "
+INSERT INTO oauth_consent (account_id, client_id, scopes)
+VALUES                    ($1,         $2,        $3)
+ON CONFLICT (account_id, client_id)
+DO UPDATE SET scopes = EXCLUDED.scopes,
+              modified_at = NOW()
+RETURNING *
+        ",
+        account_id,
+        client_id,
+        &scopes
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
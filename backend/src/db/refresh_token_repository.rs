@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{models::refresh_token::RefreshToken, util::accounts_error::AccountsResult};
+
+use super::DB;
+
+/// Inserts a refresh token, a new family is created if no family id is provided.
+#[allow(clippy::too_many_arguments)]
+pub async fn insert(
+    transaction: &mut Transaction<'_, DB>,
+    token_hash: String,
+    family_id: Option<Uuid>,
+    account_id: Uuid,
+    client_id: String,
+    scopes: Vec<String>,
+    auth_time: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    registry: bool,
+) -> AccountsResult<RefreshToken> {
+    Ok(sqlx::query_as!(
+        RefreshToken,
+        "
+INSERT INTO refresh_token (token_hash, family_id, account_id, client_id, scopes, auth_time, expires_at, registry)
+VALUES                    ($1,         COALESCE($2, gen_random_uuid()), $3, $4, $5, $6, $7, $8)
+RETURNING *
+        ",
+        token_hash,
+        family_id,
+        account_id,
+        client_id,
+        &scopes,
+        auth_time,
+        expires_at,
+        registry
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_by_token_hash(
+    transaction: &mut Transaction<'_, DB>,
+    token_hash: &str,
+) -> AccountsResult<Option<RefreshToken>> {
+    Ok(sqlx::query_as!(
+        RefreshToken,
+        "
+SELECT *
+FROM refresh_token
+WHERE token_hash = $1
+        ",
+        token_hash
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+/// Marks the token as used, returns `None` if the token has already been used.
+pub async fn mark_used(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+) -> AccountsResult<Option<RefreshToken>> {
+    Ok(sqlx::query_as!(
+        RefreshToken,
+        "
+UPDATE refresh_token
+SET used_at = NOW(),
+    modified_at = NOW()
+WHERE id = $1 AND used_at IS NULL
+RETURNING *
+        ",
+        id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn revoke_family(
+    transaction: &mut Transaction<'_, DB>,
+    family_id: Uuid,
+) -> AccountsResult<Vec<RefreshToken>> {
+    Ok(sqlx::query_as!(
+        RefreshToken,
+        "
+UPDATE refresh_token
+SET revoked_at = NOW(),
+    modified_at = NOW()
+WHERE family_id = $1 AND revoked_at IS NULL
+RETURNING *
+        ",
+        family_id
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+pub async fn delete_expired(
+    transaction: &mut Transaction<'_, DB>,
+) -> AccountsResult<Vec<RefreshToken>> {
+    Ok(sqlx::query_as!(
+        RefreshToken,
+        "
+DELETE
+FROM refresh_token
+WHERE expires_at < NOW()
+RETURNING *
+        "
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
@@ -0,0 +1,93 @@
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{
+    models::{authority::AuthorityLevel, registry_access_rule::RegistryAccessRule},
+    util::accounts_error::AccountsResult,
+};
+
+use super::DB;
+
+pub async fn insert(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: String,
+    account_id: Option<Uuid>,
+    authority: Option<AuthorityLevel>,
+    repository: String,
+    actions: Vec<String>,
+) -> AccountsResult<RegistryAccessRule> {
+    Ok(sqlx::query_as!(
+        RegistryAccessRule,
+        r#"
+INSERT INTO registry_access_rule (client_id, account_id, authority, repository, actions)
+VALUES                           ($1,        $2,         $3,        $4,         $5)
+RETURNING id, client_id, account_id, authority AS "authority: _", repository, actions, created_at, modified_at
+        "#,
+        client_id,
+        account_id,
+        authority as _,
+        repository,
+        &actions
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_by_client(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+) -> AccountsResult<Vec<RegistryAccessRule>> {
+    Ok(sqlx::query_as!(
+        RegistryAccessRule,
+        r#"
+SELECT id, client_id, account_id, authority AS "authority: _", repository, actions, created_at, modified_at
+FROM registry_access_rule
+WHERE client_id = $1
+ORDER BY created_at
+        "#,
+        client_id
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+/// The rules which apply to the account, either directly or through its authority level.
+pub async fn get_for_account(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+    account_id: Uuid,
+    authority: AuthorityLevel,
+) -> AccountsResult<Vec<RegistryAccessRule>> {
+    Ok(sqlx::query_as!(
+        RegistryAccessRule,
+        r#"
+SELECT id, client_id, account_id, authority AS "authority: _", repository, actions, created_at, modified_at
+FROM registry_access_rule
+WHERE client_id = $1 AND (account_id = $2 OR authority = $3)
+        "#,
+        client_id,
+        account_id,
+        authority as _
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+pub async fn delete(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+    id: Uuid,
+) -> AccountsResult<Option<RegistryAccessRule>> {
+    Ok(sqlx::query_as!(
+        RegistryAccessRule,
+        r#"
+DELETE
+FROM registry_access_rule
+WHERE client_id = $1 AND id = $2
+RETURNING id, client_id, account_id, authority AS "authority: _", repository, actions, created_at, modified_at
+        "#,
+        client_id,
+        id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
@@ -0,0 +1,147 @@
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{models::signing_key::SigningKey, util::accounts_error::AccountsResult};
+
+use super::DB;
+
+// Arbitrary, but unique among the advisory locks taken by us.
+const ROTATION_LOCK_ID: i64 = 1;
+
+/// Blocks other key rotations until the transaction ends,
+/// the table may be empty so there is no row to lock instead.
+pub async fn lock_rotation(transaction: &mut Transaction<'_, DB>) -> AccountsResult<()> {
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", ROTATION_LOCK_ID)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(())
+}
+
+pub async fn insert(
+    transaction: &mut Transaction<'_, DB>,
+    kid: String,
+    algorithm: String,
+    private_key: String,
+    private_key_nonces: String,
+    public_key: String,
+) -> AccountsResult<SigningKey> {
+    Ok(sqlx::query_as!(
+        SigningKey,
+        "
+INSERT INTO signing_key (kid, algorithm, private_key, private_key_nonces, public_key)
+VALUES                  ($1,  $2,        $3,          $4,                 $5)
+RETURNING *
+        ",
+        kid,
+        algorithm,
+        private_key,
+        private_key_nonces,
+        public_key
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_active(
+    transaction: &mut Transaction<'_, DB>,
+) -> AccountsResult<Option<SigningKey>> {
+    Ok(sqlx::query_as!(
+        SigningKey,
+        "
+SELECT *
+FROM signing_key
+WHERE activated_at IS NOT NULL AND retired_at IS NULL
+ORDER BY activated_at DESC
+LIMIT 1
+        "
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+/// Returns the key that has been published but isn't used to sign anything yet.
+pub async fn get_next(transaction: &mut Transaction<'_, DB>) -> AccountsResult<Option<SigningKey>> {
+    Ok(sqlx::query_as!(
+        SigningKey,
+        "
+SELECT *
+FROM signing_key
+WHERE activated_at IS NULL AND retired_at IS NULL
+ORDER BY created_at DESC
+LIMIT 1
+        "
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn activate(transaction: &mut Transaction<'_, DB>, id: Uuid) -> AccountsResult<()> {
+    sqlx::query!(
+        "
+UPDATE signing_key
+SET activated_at = NOW(),
+    modified_at = NOW()
+WHERE id = $1
+        ",
+        id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Returns the active and next keys as well as the keys that were retired less than `published_minutes` ago.
+pub async fn get_published(
+    transaction: &mut Transaction<'_, DB>,
+    published_minutes: u64,
+) -> AccountsResult<Vec<SigningKey>> {
+    Ok(sqlx::query_as!(
+        SigningKey,
+        "
+SELECT *
+FROM signing_key
+WHERE retired_at IS NULL OR retired_at + ($1 * interval '1 minute') > NOW()
+ORDER BY created_at DESC
+        ",
+        published_minutes as f64
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+/// Retires every active key except the given one.
+pub async fn retire_active_except(
+    transaction: &mut Transaction<'_, DB>,
+    id: Uuid,
+) -> AccountsResult<Vec<SigningKey>> {
+    Ok(sqlx::query_as!(
+        SigningKey,
+        "
+UPDATE signing_key
+SET retired_at = NOW(),
+    modified_at = NOW()
+WHERE activated_at IS NOT NULL AND retired_at IS NULL AND id != $1
+RETURNING *
+        ",
+        id
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+pub async fn delete_retired(
+    transaction: &mut Transaction<'_, DB>,
+    published_minutes: u64,
+) -> AccountsResult<Vec<SigningKey>> {
+    Ok(sqlx::query_as!(
+        SigningKey,
+        "
+DELETE
+FROM signing_key
+WHERE retired_at + ($1 * interval '1 minute') < NOW()
+RETURNING *
+        ",
+        published_minutes as f64
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
@@ -0,0 +1,85 @@
+use sqlx::{types::Uuid, Transaction};
+
+use crate::{
+    models::token_exchange_policy::TokenExchangePolicy, util::accounts_error::AccountsResult,
+};
+
+use super::DB;
+
+pub async fn insert(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: String,
+    audience: String,
+    scopes: Vec<String>,
+) -> AccountsResult<TokenExchangePolicy> {
+    Ok(sqlx::query_as!(
+        TokenExchangePolicy,
+        "
+INSERT INTO token_exchange_policy (client_id, audience, scopes)
+VALUES                            ($1,        $2,       $3)
+RETURNING *
+        ",
+        client_id,
+        audience,
+        &scopes
+    )
+    .fetch_one(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_by_client(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+) -> AccountsResult<Vec<TokenExchangePolicy>> {
+    Ok(sqlx::query_as!(
+        TokenExchangePolicy,
+        "
+SELECT *
+FROM token_exchange_policy
+WHERE client_id = $1
+ORDER BY created_at
+        ",
+        client_id
+    )
+    .fetch_all(&mut **transaction)
+    .await?)
+}
+
+pub async fn get_by_audience(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+    audience: &str,
+) -> AccountsResult<Option<TokenExchangePolicy>> {
+    Ok(sqlx::query_as!(
+        TokenExchangePolicy,
+        "
+SELECT *
+FROM token_exchange_policy
+WHERE client_id = $1 AND audience = $2
+        ",
+        client_id,
+        audience
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+pub async fn delete(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+    id: Uuid,
+) -> AccountsResult<Option<TokenExchangePolicy>> {
+    Ok(sqlx::query_as!(
+        TokenExchangePolicy,
+        "
+DELETE
+FROM token_exchange_policy
+WHERE client_id = $1 AND id = $2
+RETURNING *
+        ",
+        client_id,
+        id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
@@ -13,7 +13,7 @@ use api::core::core_routes;
 use api::external::external_routes;
 use api::frontend::site_routes;
 use api::oauth::oauth_routes;
-use api::openid::openid_routes;
+use api::openid::{openid_routes, well_known_routes};
 use api::response::{ErrMsg, ResponseStatus};
 use eyre::{eyre, WrapErr};
 use rocket::http::Status;
@@ -24,6 +24,7 @@ use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::ConnectOptions;
 use tokio::task;
 
+use crate::services::signing_key_service::{self, SigningKeyCache};
 use crate::util::config::Config;
 
 mod api;
@@ -71,6 +72,12 @@ async fn main() -> eyre::Result<()> {
         .await
         .wrap_err("Failed to initialize db")?;
 
+    // Make sure that there is a key available to sign tokens with
+    let signing_keys = SigningKeyCache::default();
+    signing_key_service::rotate_signing_keys(&db_pool, &config, &signing_keys)
+        .await
+        .wrap_err("Failed to setup signing keys")?;
+
     // Setup Redis cache
     let redis_client = redis::Client::open(config.redis_url.clone()).wrap_err(eyre!(
         "Failed to connect to redis on URL {:?}",
@@ -89,7 +96,11 @@ async fn main() -> eyre::Result<()> {
 
     // Setup background tasks
     let pool_clone = db_pool.clone();
-    task::spawn(background_task::run_background_tasks(pool_clone));
+    task::spawn(background_task::run_background_tasks(
+        pool_clone,
+        config.clone(),
+        signing_keys.clone(),
+    ));
 
     let rocket = rocket::build()
         .mount("/api/core", core_routes())
@@ -97,11 +108,13 @@ async fn main() -> eyre::Result<()> {
         .mount("/api/oauth", oauth_routes())
         .mount("/api/openid", openid_routes())
         .mount("/api/external", external_routes())
+        .mount("/", well_known_routes())
         .mount("/api/public", FileServer::from("static/public"))
         .register("/", catchers![unauthorized, forbidden])
         .manage(db_pool.clone())
         .manage(redis_pool)
         .manage(config)
+        .manage(signing_keys)
         .attach(Template::fairing());
 
     rocket.launch().await?;
@@ -1,6 +1,6 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, sqlx::Type, Serialize)]
+#[derive(Debug, Clone, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(type_name = "AUTHORITY_LEVEL", rename_all = "snake_case")]
 pub enum AuthorityLevel {
     User,
@@ -0,0 +1,14 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BackchannelLogout {
+    pub id: Uuid,
+    pub client_id: String,
+    pub account_id: Uuid,
+    pub sid: Option<String>,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
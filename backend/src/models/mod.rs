@@ -2,9 +2,16 @@ pub mod account;
 pub mod activation_code;
 pub mod authority;
 pub mod authorization_code;
+pub mod backchannel_logout;
 pub mod login_details;
 pub mod login_provider;
 pub mod oauth_client;
+pub mod oauth_client_secret;
+pub mod oauth_consent;
 pub mod password_reset;
+pub mod refresh_token;
+pub mod registry_access_rule;
+pub mod signing_key;
 pub mod third_party_login;
+pub mod token_exchange_policy;
 pub mod whitelist;
@@ -5,9 +5,17 @@ use sqlx::types::Uuid;
 pub struct OauthClient {
     pub id: Uuid,
     pub client_id: String,
-    pub client_secret: String,
     pub client_name: String,
-    pub redirect_uri: String,
     pub created_at: DateTime<Utc>,
     pub modified_at: DateTime<Utc>,
+    pub allowed_scopes: Vec<String>,
+    pub require_pkce: bool,
+    pub public_client: bool,
+    pub redirect_uris: Vec<String>,
+    pub first_party: bool,
+    pub post_logout_redirect_uris: Vec<String>,
+    pub backchannel_logout_uri: Option<String>,
+    pub registration_token_hash: Option<String>,
+    pub jwt_access_tokens: bool,
+    pub require_pushed_authorization_requests: bool,
 }
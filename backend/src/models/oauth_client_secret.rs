@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OauthClientSecret {
+    pub id: Uuid,
+    pub oauth_client_id: Uuid,
+    pub secret_hash: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
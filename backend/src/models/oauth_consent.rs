@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OauthConsent {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
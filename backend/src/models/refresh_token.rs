@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub token_hash: String,
+    pub family_id: Uuid,
+    pub account_id: Uuid,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub auth_time: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub registry: bool,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
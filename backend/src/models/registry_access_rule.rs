@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+use super::authority::AuthorityLevel;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RegistryAccessRule {
+    pub id: Uuid,
+    pub client_id: String,
+    pub account_id: Option<Uuid>,
+    pub authority: Option<AuthorityLevel>,
+    pub repository: String,
+    pub actions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
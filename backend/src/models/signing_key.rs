@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SigningKey {
+    pub id: Uuid,
+    pub kid: String,
+    pub algorithm: String,
+    pub private_key: String,
+    pub private_key_nonces: String,
+    pub public_key: String,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
@@ -0,0 +1,331 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, Utc};
+use jwt::{AlgorithmType, JoseHeader, SignWithKey, Token};
+use mobc_redis::RedisConnectionManager;
+use openssl::sha::sha256;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rocket::State;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::Pool;
+use tokio::task;
+use uuid::Uuid;
+
+use crate::{
+    db::{backchannel_logout_repository, new_transaction, oauth_client_repository, DB},
+    models::backchannel_logout::BackchannelLogout,
+    util::{accounts_error::AccountsError, config::Config},
+};
+
+use super::{
+    password_service::to_hex,
+    redis_service::{self, RedisError},
+    signing_key_service::{self, SigningKeyCache, SigningKeyError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackchannelLogoutError {
+    #[error("The client responded with status {0}")]
+    DeliveryFailed(u16),
+    #[error("JWT error")]
+    JwtError(#[from] jwt::Error),
+    #[error("Signing key error")]
+    SigningKeyError(#[from] SigningKeyError),
+    #[error("Reqwest error")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("Redis error")]
+    RedisError(#[from] RedisError),
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+const SESSION_CLIENTS_KEY_PREFIX: &str = "session_clients";
+// Sessions last at most 5 days
+const SESSION_CLIENTS_EXPIRATION_SECONDS: usize = 60 * 60 * 24 * 5;
+
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+const LOGOUT_TOKEN_PARAM: &str = "logout_token";
+const LOGOUT_TOKEN_ID_LENGTH: usize = 32;
+// 2 minutes
+const LOGOUT_TOKEN_EXPIRATION_SECONDS: i64 = 60 * 2;
+const REQUEST_TIMEOUT_SECONDS: u64 = 5;
+
+// Retries are made by the background tasks, which run every 30 minutes,
+// so failed deliveries are retried at their first run at least 30, 60, 120, 240 and 480 minutes
+// after the previous attempt before giving up.
+const MAX_DELIVERY_ATTEMPTS: i32 = 6;
+const RETRY_BASE_MINUTES: i64 = 30;
+
+// Keeps logout tokens from being mistaken for ID tokens (OpenID Connect Back-Channel Logout section 2.4).
+const LOGOUT_TOKEN_TYPE: &str = "logout+jwt";
+
+#[derive(Serialize, Debug)]
+struct LogoutTokenHeader {
+    alg: AlgorithmType,
+    kid: String,
+    typ: String,
+}
+
+impl JoseHeader for LogoutTokenHeader {
+    fn algorithm_type(&self) -> AlgorithmType {
+        self.alg
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        Some(&self.kid)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct LogoutTokenClaims {
+    iss: String,
+    aud: String,
+    iat: i64,
+    exp: i64,
+    jti: String,
+    sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    events: serde_json::Value,
+}
+
+/// The session id sent to clients, the actual id can't be used since it authenticates the user.
+pub fn session_sid(session_id: &str) -> String {
+    to_hex(&sha256(session_id.as_bytes()))
+}
+
+/// Remembers that the session issued tokens to the client so that the client can be told
+/// when the session ends.
+pub async fn register_session_client(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    session_id: &str,
+    client_id: &str,
+) -> Result<(), BackchannelLogoutError> {
+    let key = format!("{}:{}", SESSION_CLIENTS_KEY_PREFIX, session_id);
+    redis_service::redis_push(redis_pool, key.clone(), client_id.to_string()).await?;
+    redis_service::redis_expire(redis_pool, key, SESSION_CLIENTS_EXPIRATION_SECONDS).await?;
+
+    Ok(())
+}
+
+/// Queues a logout notification for every client the session issued tokens to,
+/// a first delivery attempt is made right away and failures are retried by the background tasks.
+pub async fn logout_session(
+    db_pool: &Pool<DB>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    session_id: &str,
+    account_id: Uuid,
+) -> Result<(), BackchannelLogoutError> {
+    let key = format!("{}:{}", SESSION_CLIENTS_KEY_PREFIX, session_id);
+    let mut client_ids = redis_service::redis_get_list(redis_pool, key.clone()).await?;
+    redis_service::redis_del(redis_pool, key).await?;
+
+    client_ids.sort();
+    client_ids.dedup();
+
+    let sid = session_sid(session_id);
+    // Postponed so that the background tasks leave the first attempt alone.
+    let next_attempt_at = Utc::now() + Duration::minutes(RETRY_BASE_MINUTES);
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let mut logouts = Vec::new();
+    for client_id in client_ids {
+        let Some(client) =
+            oauth_client_repository::get_by_client_id(&mut transaction, &client_id).await?
+        else {
+            continue;
+        };
+
+        if client.backchannel_logout_uri.is_none() {
+            continue;
+        }
+
+        let logout = backchannel_logout_repository::insert(
+            &mut transaction,
+            client.client_id,
+            account_id,
+            Some(sid.clone()),
+            next_attempt_at,
+        )
+        .await?;
+        logouts.push(logout);
+    }
+
+    transaction.commit().await?;
+
+    if !logouts.is_empty() {
+        let db_pool = db_pool.clone();
+        let config = config.clone();
+        let signing_keys = signing_keys.clone();
+        task::spawn(async move {
+            if let Err(err) = deliver_logouts(&db_pool, &config, &signing_keys, logouts).await {
+                error!("Failed to deliver backchannel logouts, err: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Queues logout notifications for all of the account's sessions.
+pub async fn logout_sessions(
+    db_pool: &Pool<DB>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    session_ids: &[String],
+    account_id: Uuid,
+) -> Result<(), BackchannelLogoutError> {
+    for session_id in session_ids {
+        logout_session(
+            db_pool,
+            redis_pool,
+            config,
+            signing_keys,
+            session_id,
+            account_id,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Retries the logout notifications whose previous delivery failed.
+pub async fn retry_failed_logouts(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+) -> Result<(), BackchannelLogoutError> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let logouts = backchannel_logout_repository::get_due(&mut transaction).await?;
+    transaction.commit().await?;
+
+    deliver_logouts(db_pool, config, signing_keys, logouts).await
+}
+
+async fn deliver_logouts(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    logouts: Vec<BackchannelLogout>,
+) -> Result<(), BackchannelLogoutError> {
+    let http_client = reqwest::Client::builder()
+        .timeout(StdDuration::from_secs(REQUEST_TIMEOUT_SECONDS))
+        .build()?;
+
+    for logout in logouts {
+        let result = send_logout_token(db_pool, config, signing_keys, &http_client, &logout).await;
+
+        let mut transaction = new_transaction(db_pool).await?;
+        match result {
+            Ok(()) => {
+                backchannel_logout_repository::delete(&mut transaction, logout.id).await?;
+            }
+            Err(err) if logout.attempts + 1 >= MAX_DELIVERY_ATTEMPTS => {
+                error!(
+                    "Giving up on backchannel logout to client {}, err: {}",
+                    logout.client_id, err
+                );
+                backchannel_logout_repository::delete(&mut transaction, logout.id).await?;
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to deliver backchannel logout to client {}, err: {}",
+                    logout.client_id, err
+                );
+                let next_attempt_at =
+                    Utc::now() + Duration::minutes(RETRY_BASE_MINUTES << logout.attempts);
+                backchannel_logout_repository::reschedule(
+                    &mut transaction,
+                    logout.id,
+                    next_attempt_at,
+                )
+                .await?;
+            }
+        }
+        transaction.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Sends the logout token (OpenID Connect Back-Channel Logout section 2.5),
+/// nothing is sent if the client no longer wants to be notified.
+async fn send_logout_token(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    http_client: &reqwest::Client,
+    logout: &BackchannelLogout,
+) -> Result<(), BackchannelLogoutError> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let client =
+        oauth_client_repository::get_by_client_id(&mut transaction, &logout.client_id).await?;
+    transaction.commit().await?;
+
+    let Some(backchannel_logout_uri) = client.and_then(|client| client.backchannel_logout_uri)
+    else {
+        return Ok(());
+    };
+
+    let logout_token = create_logout_token(db_pool, config, signing_keys, logout).await?;
+
+    let response = http_client
+        .post(backchannel_logout_uri)
+        .form(&[(LOGOUT_TOKEN_PARAM, logout_token)])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(BackchannelLogoutError::DeliveryFailed(
+            response.status().as_u16(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn create_logout_token(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    logout: &BackchannelLogout,
+) -> Result<String, BackchannelLogoutError> {
+    let now = Utc::now();
+    let claims = LogoutTokenClaims {
+        iss: config.backend_address.clone(),
+        aud: logout.client_id.clone(),
+        iat: now.timestamp(),
+        exp: now.timestamp() + LOGOUT_TOKEN_EXPIRATION_SECONDS,
+        jti: generate_token_id(),
+        sub: logout.account_id.to_string(),
+        sid: logout.sid.clone(),
+        events: json!({ BACKCHANNEL_LOGOUT_EVENT: {} }),
+    };
+
+    let signing_key = signing_key_service::get_signing_key(db_pool, config, signing_keys).await?;
+
+    let header = LogoutTokenHeader {
+        alg: AlgorithmType::Rs256,
+        kid: signing_key.kid,
+        typ: LOGOUT_TOKEN_TYPE.to_string(),
+    };
+
+    let token = Token::new(header, claims).sign_with_key(&signing_key.key)?;
+    Ok(token.as_str().to_string())
+}
+
+fn generate_token_id() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(LOGOUT_TOKEN_ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
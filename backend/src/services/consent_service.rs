@@ -0,0 +1,74 @@
+use rocket::State;
+use sqlx::Pool;
+use uuid::Uuid;
+
+use crate::{
+    db::{new_transaction, oauth_consent_repository, DB},
+    util::{accounts_error::AccountsError, scope},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConsentError {
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+/// Whether the account has already consented to sharing all of the scopes with the client.
+pub async fn has_consented(
+    db_pool: &State<Pool<DB>>,
+    account_id: Uuid,
+    client_id: &str,
+    scopes: &[String],
+) -> Result<bool, ConsentError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let consent = oauth_consent_repository::get_by_account_and_client(
+        &mut transaction,
+        account_id,
+        client_id,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(consent.is_some_and(|consent| {
+        scopes
+            .iter()
+            .all(|requested| scope::has_scope(&consent.scopes, requested))
+    }))
+}
+
+/// Remembers that the account has consented to the scopes,
+/// previously granted scopes are kept so that the user isn't asked for them again.
+pub async fn grant_consent(
+    db_pool: &State<Pool<DB>>,
+    account_id: Uuid,
+    client_id: String,
+    scopes: &[String],
+) -> Result<(), ConsentError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let mut granted_scopes = oauth_consent_repository::get_by_account_and_client(
+        &mut transaction,
+        account_id,
+        &client_id,
+    )
+    .await?
+    .map(|consent| consent.scopes)
+    .unwrap_or_default();
+
+    for requested in scopes {
+        if !scope::has_scope(&granted_scopes, requested) {
+            granted_scopes.push(requested.clone());
+        }
+    }
+
+    oauth_consent_repository::upsert(&mut transaction, account_id, client_id, granted_scopes)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use chrono::{DateTime, Duration, Utc};
+use mobc_redis::RedisConnectionManager;
+use rand::{distributions::Alphanumeric, seq::SliceRandom, thread_rng, Rng};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::redis_service::{self, RedisError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeviceAuthorizationError {
+    #[error("The user has not yet approved the device")]
+    AuthorizationPending,
+    #[error("The device is polling too frequently")]
+    SlowDown,
+    #[error("The user denied the device authorization")]
+    AccessDenied,
+    #[error("The device code is invalid or has expired")]
+    ExpiredToken,
+    #[error("The user code is invalid or has expired")]
+    InvalidUserCode,
+    #[error("Failed to generate expiration time")]
+    ExpirationTimeGeneration,
+    #[error("Redis error")]
+    RedisError(#[from] RedisError),
+}
+
+const DEVICE_CODE_LENGTH: usize = 48;
+const DEVICE_CODE_KEY_REDIS_PREFIX: &str = "device_codes";
+const USER_CODE_KEY_REDIS_PREFIX: &str = "device_user_codes";
+// 10 minutes
+const DEVICE_CODE_EXPIRATION_SECONDS: i64 = 10 * 60;
+
+// Consonants only, to avoid spelling words and characters that are easily confused (RFC 8628 section 6.1).
+const USER_CODE_CHARACTERS: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+const POLLING_INTERVAL_SECONDS: i64 = 5;
+// The amount the interval is increased by every time the device polls too quickly.
+const SLOW_DOWN_INCREMENT_SECONDS: i64 = 5;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum DeviceAuthorizationStatus {
+    Pending,
+    Approved {
+        account_id: Uuid,
+        auth_time: DateTime<Utc>,
+    },
+    Denied,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub status: DeviceAuthorizationStatus,
+    pub interval_seconds: i64,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub expiration: DateTime<Utc>,
+}
+
+/// Starts a device authorization (RFC 8628), the client has already been authenticated.
+pub async fn create_device_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    scopes: Vec<String>,
+) -> Result<DeviceAuthorization, DeviceAuthorizationError> {
+    let device_code: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(DEVICE_CODE_LENGTH)
+        .map(char::from)
+        .collect();
+
+    let user_code = generate_user_code();
+
+    let expiration = Utc::now()
+        .checked_add_signed(Duration::seconds(DEVICE_CODE_EXPIRATION_SECONDS))
+        .ok_or(DeviceAuthorizationError::ExpirationTimeGeneration)?;
+
+    let device_authorization = DeviceAuthorization {
+        device_code: device_code.clone(),
+        user_code: user_code.clone(),
+        client_id,
+        scopes,
+        status: DeviceAuthorizationStatus::Pending,
+        interval_seconds: POLLING_INTERVAL_SECONDS,
+        last_polled_at: None,
+        expiration,
+    };
+
+    save_device_authorization(redis_pool, &device_authorization).await?;
+
+    redis_service::redis_set(
+        redis_pool,
+        format!("{}:{}", USER_CODE_KEY_REDIS_PREFIX, user_code),
+        device_code,
+        DEVICE_CODE_EXPIRATION_SECONDS as usize,
+    )
+    .await?;
+
+    Ok(device_authorization)
+}
+
+fn generate_user_code() -> String {
+    let mut rng = thread_rng();
+    (0..USER_CODE_LENGTH)
+        .filter_map(|_| USER_CODE_CHARACTERS.choose(&mut rng))
+        .map(|c| *c as char)
+        .collect()
+}
+
+/// Formats the user code the way it is shown to the user, e.g. `BCDF-GHJK`.
+pub fn format_user_code(user_code: &str) -> String {
+    let (first, second) = user_code.split_at(user_code.len() / 2);
+    format!("{first}-{second}")
+}
+
+/// Looks up the device authorization for a user code entered by the user,
+/// the code is case-insensitive and any separators are ignored.
+pub async fn get_by_user_code(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    user_code: &str,
+) -> Result<DeviceAuthorization, DeviceAuthorizationError> {
+    let user_code: String = user_code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    let device_code: String = redis_service::redis_get_option(
+        redis_pool,
+        format!("{}:{}", USER_CODE_KEY_REDIS_PREFIX, user_code),
+    )
+    .await?
+    .ok_or(DeviceAuthorizationError::InvalidUserCode)?;
+
+    get_device_authorization(redis_pool, &device_code)
+        .await?
+        .ok_or(DeviceAuthorizationError::InvalidUserCode)
+}
+
+/// Records the user's decision, the user code can only be used once.
+pub async fn complete_device_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    mut device_authorization: DeviceAuthorization,
+    account_id: Uuid,
+    auth_time: DateTime<Utc>,
+    approved: bool,
+) -> Result<(), DeviceAuthorizationError> {
+    if !matches!(
+        device_authorization.status,
+        DeviceAuthorizationStatus::Pending
+    ) {
+        return Err(DeviceAuthorizationError::InvalidUserCode);
+    }
+
+    device_authorization.status = if approved {
+        DeviceAuthorizationStatus::Approved {
+            account_id,
+            auth_time,
+        }
+    } else {
+        DeviceAuthorizationStatus::Denied
+    };
+
+    redis_service::redis_del(
+        redis_pool,
+        format!(
+            "{}:{}",
+            USER_CODE_KEY_REDIS_PREFIX, device_authorization.user_code
+        ),
+    )
+    .await?;
+
+    save_device_authorization(redis_pool, &device_authorization).await
+}
+
+/// Called when the device polls the token endpoint.
+/// Returns the device authorization once it has been approved, after which the device code can't be used again.
+pub async fn poll_device_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: &str,
+    device_code: &str,
+) -> Result<DeviceAuthorization, DeviceAuthorizationError> {
+    let mut device_authorization = get_device_authorization(redis_pool, device_code)
+        .await?
+        .ok_or(DeviceAuthorizationError::ExpiredToken)?;
+
+    if device_authorization.client_id != client_id {
+        error!(
+            "Device code issued to client {} was presented by client {}",
+            device_authorization.client_id, client_id
+        );
+        return Err(DeviceAuthorizationError::ExpiredToken);
+    }
+
+    let now = Utc::now();
+    let polled_too_soon = device_authorization
+        .last_polled_at
+        .is_some_and(|last_polled_at| {
+            now - last_polled_at < Duration::seconds(device_authorization.interval_seconds)
+        });
+    device_authorization.last_polled_at = Some(now);
+
+    match device_authorization.status {
+        DeviceAuthorizationStatus::Pending if polled_too_soon => {
+            device_authorization.interval_seconds += SLOW_DOWN_INCREMENT_SECONDS;
+            save_device_authorization(redis_pool, &device_authorization).await?;
+            Err(DeviceAuthorizationError::SlowDown)
+        }
+        DeviceAuthorizationStatus::Pending => {
+            save_device_authorization(redis_pool, &device_authorization).await?;
+            Err(DeviceAuthorizationError::AuthorizationPending)
+        }
+        DeviceAuthorizationStatus::Denied => {
+            delete_device_authorization(redis_pool, device_code).await?;
+            Err(DeviceAuthorizationError::AccessDenied)
+        }
+        DeviceAuthorizationStatus::Approved { .. } => {
+            delete_device_authorization(redis_pool, device_code).await?;
+            Ok(device_authorization)
+        }
+    }
+}
+
+async fn get_device_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    device_code: &str,
+) -> Result<Option<DeviceAuthorization>, DeviceAuthorizationError> {
+    let key = format!("{}:{}", DEVICE_CODE_KEY_REDIS_PREFIX, device_code);
+    Ok(redis_service::redis_get_option(redis_pool, key).await?)
+}
+
+async fn save_device_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    device_authorization: &DeviceAuthorization,
+) -> Result<(), DeviceAuthorizationError> {
+    let expiration_seconds = (device_authorization.expiration - Utc::now()).num_seconds();
+    if expiration_seconds <= 0 {
+        return Err(DeviceAuthorizationError::ExpiredToken);
+    }
+
+    let key = format!(
+        "{}:{}",
+        DEVICE_CODE_KEY_REDIS_PREFIX, device_authorization.device_code
+    );
+    redis_service::redis_set(
+        redis_pool,
+        key,
+        device_authorization.clone(),
+        expiration_seconds as usize,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn delete_device_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    device_code: &str,
+) -> Result<(), DeviceAuthorizationError> {
+    let key = format!("{}:{}", DEVICE_CODE_KEY_REDIS_PREFIX, device_code);
+    redis_service::redis_del(redis_pool, key).await?;
+    Ok(())
+}
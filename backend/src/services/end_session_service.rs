@@ -0,0 +1,134 @@
+use rocket::{http::RawStr, State};
+use sqlx::Pool;
+use uuid::Uuid;
+
+use crate::{
+    db::{new_transaction, oauth_client_repository, DB},
+    util::{accounts_error::AccountsError, config::Config},
+};
+
+use super::{
+    id_token_service::{self, IdTokenError},
+    signing_key_service::SigningKeyCache,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EndSessionError {
+    #[error("The ID token hint is invalid")]
+    InvalidIdTokenHint,
+    #[error("The client ID did not match the ID token hint")]
+    ClientIdMismatch,
+    #[error("There is no client with that client_id")]
+    NoClientWithId,
+    #[error("The post logout redirect uri is not registered for the client")]
+    InvalidPostLogoutRedirectUri,
+    #[error("ID token error")]
+    IdTokenError(IdTokenError),
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+/// A validated logout request from a relying party.
+#[derive(Debug)]
+pub struct EndSession {
+    /// The account the ID token hint was issued to, only that account should be logged out.
+    pub account_id: Option<Uuid>,
+    pub redirect_uri: Option<String>,
+}
+
+/// Validates an RP-initiated logout request (OpenID Connect RP-Initiated Logout section 2).
+/// The post logout redirect uri must be registered for the client,
+/// which is identified by the ID token hint or the client id.
+pub async fn end_session(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    id_token_hint: Option<&str>,
+    client_id: Option<String>,
+    post_logout_redirect_uri: Option<String>,
+    state: Option<&str>,
+) -> Result<EndSession, EndSessionError> {
+    let hint = match id_token_hint {
+        Some(id_token_hint) => {
+            match id_token_service::verify_id_token_hint(
+                db_pool,
+                config,
+                signing_keys,
+                id_token_hint,
+            )
+            .await
+            {
+                Ok(hint) => Some(hint),
+                Err(IdTokenError::InvalidToken | IdTokenError::JwtError(_)) => {
+                    return Err(EndSessionError::InvalidIdTokenHint)
+                }
+                Err(err) => return Err(EndSessionError::IdTokenError(err)),
+            }
+        }
+        None => None,
+    };
+
+    let client_id = match (&hint, client_id) {
+        (Some(hint), Some(client_id)) if hint.client_id != client_id => {
+            error!(
+                "Client id {} did not match the ID token hint audience {}",
+                client_id, hint.client_id
+            );
+            return Err(EndSessionError::ClientIdMismatch);
+        }
+        (Some(hint), _) => Some(hint.client_id.clone()),
+        (None, client_id) => client_id,
+    };
+
+    let account_id = hint.map(|hint| hint.account_id);
+
+    let Some(post_logout_redirect_uri) = post_logout_redirect_uri else {
+        return Ok(EndSession {
+            account_id,
+            redirect_uri: None,
+        });
+    };
+
+    let client_id = client_id.ok_or(EndSessionError::InvalidPostLogoutRedirectUri)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let client = oauth_client_repository::get_by_client_id(&mut transaction, &client_id)
+        .await?
+        .ok_or(EndSessionError::NoClientWithId)?;
+    transaction.commit().await?;
+
+    if !client
+        .post_logout_redirect_uris
+        .contains(&post_logout_redirect_uri)
+    {
+        error!(
+            "Post logout redirect uri {} is not registered for client {}",
+            post_logout_redirect_uri, client_id
+        );
+        return Err(EndSessionError::InvalidPostLogoutRedirectUri);
+    }
+
+    let redirect_uri = match state {
+        Some(state) => {
+            let separator = if post_logout_redirect_uri.contains('?') {
+                '&'
+            } else {
+                '?'
+            };
+            format!(
+                "{}{}state={}",
+                post_logout_redirect_uri,
+                separator,
+                RawStr::new(state).percent_encode()
+            )
+        }
+        None => post_logout_redirect_uri,
+    };
+
+    Ok(EndSession {
+        account_id,
+        redirect_uri: Some(redirect_uri),
+    })
+}
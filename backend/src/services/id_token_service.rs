@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use jwt::{AlgorithmType, Header, SignWithKey, Token, Unverified, VerifyWithKey};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use sqlx::Pool;
+use uuid::Uuid;
+
+use crate::{
+    db::{login_details_repository, new_transaction, DB},
+    util::{
+        accounts_error::AccountsError,
+        config::Config,
+        scope::{self, SCOPE_EMAIL},
+        uuid::uuid_to_sqlx,
+    },
+};
+
+use super::signing_key_service::{self, SigningKeyCache, SigningKeyError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdTokenError {
+    #[error("Login details not found")]
+    LoginDetailsNotFound,
+    #[error("The ID token was not issued by us")]
+    InvalidToken,
+    #[error("JWT error")]
+    JwtError(#[from] jwt::Error),
+    #[error("Signing key error")]
+    SigningKeyError(#[from] SigningKeyError),
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+#[derive(Serialize, Debug)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: String,
+    exp: i64,
+    iat: i64,
+    auth_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email_verified: Option<bool>,
+}
+
+/// Creates a signed OpenID Connect ID token for the account, valid until the given expiration.
+/// The email claims are only included if the email scope has been granted.
+/// The nonce from the authorization request is echoed back so that the client can detect replays,
+/// the sid identifies the session for back-channel logout.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_id_token(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    client_id: String,
+    account_id: Uuid,
+    auth_time: DateTime<Utc>,
+    expiration: DateTime<Utc>,
+    scopes: &[String],
+    nonce: Option<String>,
+    sid: Option<String>,
+) -> Result<String, IdTokenError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let login_details =
+        login_details_repository::get_by_account_id(&mut transaction, uuid_to_sqlx(account_id))
+            .await?
+            .ok_or(IdTokenError::LoginDetailsNotFound)?;
+
+    transaction.commit().await?;
+
+    let include_email = scope::has_scope(scopes, SCOPE_EMAIL);
+
+    let claims = IdTokenClaims {
+        iss: config.backend_address.clone(),
+        sub: account_id.to_string(),
+        aud: client_id,
+        exp: expiration.timestamp(),
+        iat: Utc::now().timestamp(),
+        auth_time: auth_time.timestamp(),
+        nonce,
+        sid,
+        email: include_email.then_some(login_details.email),
+        email_verified: include_email.then_some(login_details.activated_at.is_some()),
+    };
+
+    let signing_key = signing_key_service::get_signing_key(db_pool, config, signing_keys).await?;
+
+    let header = Header {
+        algorithm: AlgorithmType::Rs256,
+        key_id: Some(signing_key.kid),
+        ..Default::default()
+    };
+
+    let token = Token::new(header, claims).sign_with_key(&signing_key.key)?;
+    Ok(token.as_str().to_string())
+}
+
+#[derive(Deserialize, Debug)]
+struct IdTokenHintClaims {
+    iss: String,
+    sub: Uuid,
+    aud: String,
+}
+
+/// The account and client an ID token previously issued by us was for.
+#[derive(Debug)]
+pub struct IdTokenHint {
+    pub account_id: Uuid,
+    pub client_id: String,
+}
+
+/// Verifies an ID token sent back to us as a hint, the token may have expired.
+pub async fn verify_id_token_hint(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    id_token: &str,
+) -> Result<IdTokenHint, IdTokenError> {
+    // Our other tokens are signed with the same keys, but their own `typ` isn't accepted by the standard header.
+    let token: Token<Header, IdTokenHintClaims, Unverified> = Token::parse_unverified(id_token)?;
+
+    let kid = token
+        .header()
+        .key_id
+        .clone()
+        .ok_or(IdTokenError::InvalidToken)?;
+
+    let key = signing_key_service::get_verifying_key(db_pool, signing_keys, &kid)
+        .await?
+        .ok_or(IdTokenError::InvalidToken)?;
+
+    let token = token.verify_with_key(&key)?;
+    let claims = token.claims();
+
+    if claims.iss != config.backend_address {
+        return Err(IdTokenError::InvalidToken);
+    }
+
+    Ok(IdTokenHint {
+        account_id: claims.sub,
+        client_id: claims.aud.clone(),
+    })
+}
@@ -0,0 +1,173 @@
+use chrono::{DateTime, TimeZone, Utc};
+use jwt::{AlgorithmType, JoseHeader, SignWithKey, Token, Unverified, VerifyWithKey};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use sqlx::Pool;
+use uuid::Uuid;
+
+use crate::{
+    db::DB,
+    util::{config::Config, scope},
+};
+
+use super::{
+    oauth2_authorization_service::{AccessToken, Actor},
+    signing_key_service::{self, SigningKeyCache, SigningKeyError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtAccessTokenError {
+    #[error("The access token was not issued by us or has expired")]
+    InvalidToken,
+    #[error("JWT error")]
+    JwtError(#[from] jwt::Error),
+    #[error("Signing key error")]
+    SigningKeyError(#[from] SigningKeyError),
+}
+
+// Distinguishes access tokens from other JWTs signed with the same key, such as ID tokens.
+const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+const ACCESS_TOKEN_ID_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AccessTokenHeader {
+    alg: AlgorithmType,
+    kid: String,
+    typ: String,
+}
+
+impl JoseHeader for AccessTokenHeader {
+    fn algorithm_type(&self) -> AlgorithmType {
+        self.alg
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        Some(&self.kid)
+    }
+}
+
+/// The claims of the JWT profile for access tokens (RFC 9068 section 2.2).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AccessTokenClaims {
+    iss: String,
+    exp: i64,
+    /// The service the token was exchanged for, otherwise the client itself.
+    aud: String,
+    /// The account, or the client itself for client credentials tokens.
+    sub: String,
+    client_id: String,
+    iat: i64,
+    jti: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    scope: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>,
+}
+
+/// Opaque access tokens are alphanumeric, so anything with the three parts of a JWT is one.
+pub fn is_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+/// Signs the access token, the token is intended for the client's own resource servers
+/// unless it has been exchanged for another audience.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_jwt_access_token(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    client_id: &str,
+    account_id: Option<Uuid>,
+    scopes: &[String],
+    issued_at: DateTime<Utc>,
+    expiration: DateTime<Utc>,
+    audience: Option<&str>,
+    act: Option<&Actor>,
+) -> Result<String, JwtAccessTokenError> {
+    let claims = AccessTokenClaims {
+        iss: config.backend_address.clone(),
+        exp: expiration.timestamp(),
+        aud: audience.unwrap_or(client_id).to_string(),
+        sub: account_id
+            .map(|account_id| account_id.to_string())
+            .unwrap_or_else(|| client_id.to_string()),
+        client_id: client_id.to_string(),
+        iat: issued_at.timestamp(),
+        jti: generate_token_id(),
+        scope: scope::format_scopes(scopes),
+        act: act.cloned(),
+    };
+
+    let signing_key = signing_key_service::get_signing_key(db_pool, config, signing_keys).await?;
+
+    let header = AccessTokenHeader {
+        alg: AlgorithmType::Rs256,
+        kid: signing_key.kid,
+        typ: ACCESS_TOKEN_TYPE.to_string(),
+    };
+
+    let token = Token::new(header, claims).sign_with_key(&signing_key.key)?;
+    Ok(token.as_str().to_string())
+}
+
+/// Verifies a JWT access token issued by us, the token has to be signed with one of our published keys
+/// and must not have expired.
+pub async fn verify_jwt_access_token(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    token: &str,
+) -> Result<AccessToken, JwtAccessTokenError> {
+    let unverified: Token<AccessTokenHeader, AccessTokenClaims, Unverified> =
+        Token::parse_unverified(token)?;
+
+    if unverified.header().typ != ACCESS_TOKEN_TYPE {
+        return Err(JwtAccessTokenError::InvalidToken);
+    }
+
+    let key =
+        signing_key_service::get_verifying_key(db_pool, signing_keys, &unverified.header().kid)
+            .await?
+            .ok_or(JwtAccessTokenError::InvalidToken)?;
+
+    let verified = unverified.verify_with_key(&key)?;
+    let claims = verified.claims().clone();
+
+    let (Some(expiration), Some(issued_at)) = (
+        Utc.timestamp_opt(claims.exp, 0).single(),
+        Utc.timestamp_opt(claims.iat, 0).single(),
+    ) else {
+        return Err(JwtAccessTokenError::InvalidToken);
+    };
+
+    if claims.iss != config.backend_address || expiration <= Utc::now() {
+        return Err(JwtAccessTokenError::InvalidToken);
+    }
+
+    let account_id = if claims.sub == claims.client_id {
+        None
+    } else {
+        Some(Uuid::parse_str(&claims.sub).map_err(|_| JwtAccessTokenError::InvalidToken)?)
+    };
+
+    let audience = (claims.aud != claims.client_id).then_some(claims.aud);
+
+    Ok(AccessToken {
+        access_token: token.to_string(),
+        expiration,
+        issued_at,
+        client_id: claims.client_id,
+        account_id,
+        scopes: scope::parse_scopes(&claims.scope),
+        audience,
+        act: claims.act,
+    })
+}
+
+fn generate_token_id() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(ACCESS_TOKEN_ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
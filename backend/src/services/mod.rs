@@ -1,13 +1,24 @@
 pub mod activate_account_service;
+pub mod backchannel_logout_service;
+pub mod consent_service;
 pub mod create_account_service;
+pub mod device_authorization_service;
 pub mod email_service;
+pub mod end_session_service;
 pub mod google_api_service;
+pub mod id_token_service;
+pub mod jwt_access_token_service;
 pub mod login_service;
 pub mod oauth2_authorization_service;
 pub mod oauth_client_service;
 pub mod password_service;
+pub mod pushed_authorization_service;
 pub mod redis_service;
+pub mod refresh_token_service;
+pub mod registry_access_service;
 pub mod reset_password_service;
+pub mod signing_key_service;
+pub mod token_exchange_service;
 pub mod user_info_service;
 pub mod user_service;
 pub mod users_service;
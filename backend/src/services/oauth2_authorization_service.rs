@@ -1,17 +1,41 @@
 use chrono::{DateTime, Duration, Utc};
 use mobc_redis::RedisConnectionManager;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use rocket::State;
+use rocket::{http::RawStr, State};
 use serde::{Deserialize, Serialize};
 use sqlx::Pool;
 use uuid::Uuid;
 
 use crate::{
+    api::auth::session_guard::Session,
     db::{new_transaction, oauth_client_repository, DB},
-    util::accounts_error::AccountsError,
+    models::oauth_client::OauthClient,
+    util::{
+        accounts_error::AccountsError,
+        config::Config,
+        pkce::CodeChallenge,
+        redirect_uri,
+        scope::{self, SCOPE_OPENID, STANDARD_SCOPES},
+    },
 };
 
-use super::redis_service::{self, RedisError};
+use super::{
+    backchannel_logout_service::{self, BackchannelLogoutError},
+    consent_service::{self, ConsentError},
+    device_authorization_service::{
+        self, DeviceAuthorization, DeviceAuthorizationError, DeviceAuthorizationStatus,
+    },
+    id_token_service::{self, IdTokenError},
+    jwt_access_token_service::{self, JwtAccessTokenError},
+    oauth_client_service,
+    pushed_authorization_service::{
+        self, AuthorizationParameters, PushedAuthorization, PushedAuthorizationError,
+    },
+    redis_service::{self, RedisError},
+    refresh_token_service::{self, RefreshTokenError},
+    signing_key_service::SigningKeyCache,
+    token_exchange_service::{self, TokenExchangeError},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Oauth2Error {
@@ -23,8 +47,20 @@ pub enum Oauth2Error {
     InvalidRedirectUri,
     #[error("Invalid client secret")]
     InvalidClientSecret,
+    #[error("Requested scope is not allowed for the client")]
+    InvalidScope,
+    #[error("The client requires PKCE but no code challenge was provided")]
+    CodeChallengeRequired,
+    #[error("Invalid code challenge provided")]
+    InvalidCodeChallenge,
+    #[error("Code verifier did not match the code challenge")]
+    InvalidCodeVerifier,
     #[error("Invalid authorization code provided")]
     InvalidCode,
+    #[error("The consent request is invalid or has expired")]
+    InvalidConsentRequest,
+    #[error("The user has not consented to the requested scopes")]
+    ConsentRequired,
     #[error("Failed to generate expiration time")]
     ExpirationTimeGeneration,
     #[error("Sqlx error")]
@@ -35,6 +71,24 @@ pub enum Oauth2Error {
     RedisError(#[from] RedisError),
     #[error("Failed to insert access token into the redis cache")]
     CacheInsertion,
+    #[error("ID token error")]
+    IdTokenError(#[from] IdTokenError),
+    #[error("Device authorization error")]
+    DeviceAuthorizationError(#[from] DeviceAuthorizationError),
+    #[error("Refresh token error")]
+    RefreshTokenError(#[from] RefreshTokenError),
+    #[error("Consent error")]
+    ConsentError(#[from] ConsentError),
+    #[error("Backchannel logout error")]
+    BackchannelLogoutError(#[from] BackchannelLogoutError),
+    #[error("JWT access token error")]
+    JwtAccessTokenError(#[from] JwtAccessTokenError),
+    #[error("Pushed authorization error")]
+    PushedAuthorizationError(#[from] PushedAuthorizationError),
+    #[error("The subject token is invalid, expired or not issued to a user")]
+    InvalidSubjectToken,
+    #[error("Token exchange error")]
+    TokenExchangeError(#[from] TokenExchangeError),
 }
 
 const AUTH_TOKEN_LENGTH: usize = 48;
@@ -43,6 +97,16 @@ const AUTHORIZATION_KEY_REDIS_PREFIX: &str = "authorization_codes";
 // 5 minutes
 const AUTHORIZATION_CODE_EXPIRATION_SECONDS: usize = 5 * 60;
 
+const CONSENT_ID_LENGTH: usize = 48;
+const CONSENT_KEY_REDIS_PREFIX: &str = "consent_requests";
+// 10 minutes
+const CONSENT_REQUEST_EXPIRATION_SECONDS: usize = 10 * 60;
+
+const REAUTHENTICATION_ID_LENGTH: usize = 48;
+const REAUTHENTICATION_KEY_REDIS_PREFIX: &str = "reauthentications";
+// 10 minutes, the time the user has to log in again.
+const REAUTHENTICATION_EXPIRATION_SECONDS: usize = 10 * 60;
+
 const ACCESS_TOKEN_LENGTH: usize = 128;
 pub const ACCESS_TOKEN_KEY_REDIS_PREFIX: &str = "access_tokens";
 // 1 hour
@@ -53,30 +117,188 @@ struct AuthToken {
     code: String,
     client_id: String,
     account_id: Uuid,
+    // The token request must use the same redirect uri as the authorization request.
+    redirect_uri: String,
+    scopes: Vec<String>,
+    auth_time: DateTime<Utc>,
+    code_challenge: Option<CodeChallenge>,
+    #[serde(default)]
+    nonce: Option<String>,
+    // Identifies the session to the client without revealing the session id.
+    #[serde(default)]
+    sid: Option<String>,
+}
+
+/// An authorization request which has been validated but not yet turned into a code.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PendingAuthorization {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub state: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: Option<CodeChallenge>,
+    pub account_id: Uuid,
+    pub auth_time: DateTime<Utc>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    // The session is told about when it ends, with back-channel logout.
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Asks the user whether they want to share the scopes with the client.
+#[derive(Debug)]
+pub struct ConsentRequest {
+    pub consent_id: String,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+}
+
+/// Whether the user should be asked for consent (OpenID Connect Core section 3.1.2.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsentPrompt {
+    /// Only if the user hasn't already consented to the scopes.
+    IfRequired,
+    Always,
+    /// Fail with a consent required error rather than asking the user.
+    Never,
+}
+
+/// Shows the user which client is asking for which scopes before they approve a device
+/// (RFC 8628 section 5.4).
+#[derive(Debug)]
+pub struct DeviceVerification {
+    pub user_code: String,
+    pub client_name: String,
+    pub scopes: Vec<String>,
 }
 
+#[derive(Debug)]
+pub enum Authorization {
+    /// The user has authorized the client, redirect them back to it with the code.
+    Redirect(String),
+    ConsentRequired(ConsentRequest),
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn get_auth_token(
     db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
     client_id: String,
     redirect_uri: String,
     state: String,
-    account_id: Uuid,
-) -> Result<String, Oauth2Error> {
-    let mut transaction = new_transaction(db_pool).await?;
+    scopes: Option<Vec<String>>,
+    code_challenge: Option<CodeChallenge>,
+    nonce: Option<String>,
+    session: &Session,
+    consent_prompt: ConsentPrompt,
+) -> Result<Authorization, Oauth2Error> {
+    let client = get_client_for_redirect_uri(db_pool, &client_id, &redirect_uri).await?;
+
+    let scopes = resolve_scopes(&client, scopes).ok_or(Oauth2Error::InvalidScope)?;
 
-    let client = oauth_client_repository::get_by_client_id(&mut transaction, &client_id)
+    if (client.require_pkce || client.public_client) && code_challenge.is_none() {
+        error!(
+            "Client {} requires PKCE but no code challenge was provided",
+            client_id
+        );
+        return Err(Oauth2Error::CodeChallengeRequired);
+    }
+
+    let pending = PendingAuthorization {
+        client_id,
+        redirect_uri,
+        state,
+        scopes,
+        code_challenge,
+        account_id: session.account_id,
+        auth_time: session.authenticated_at,
+        nonce,
+        session_id: Some(session.id.clone()),
+    };
+
+    let consented = match consent_prompt {
+        ConsentPrompt::Always => false,
+        _ => {
+            client.first_party
+                || consent_service::has_consented(
+                    db_pool,
+                    session.account_id,
+                    &client.client_id,
+                    &pending.scopes,
+                )
+                .await?
+        }
+    };
+
+    if consented {
+        let url = create_authorization_code(redis_pool, pending).await?;
+        return Ok(Authorization::Redirect(url));
+    }
+
+    if consent_prompt == ConsentPrompt::Never {
+        return Err(Oauth2Error::ConsentRequired);
+    }
+
+    let consent_id = generate_consent_id();
+    let consent_request = ConsentRequest {
+        consent_id: consent_id.clone(),
+        client_name: client.client_name,
+        scopes: pending.scopes.clone(),
+    };
+
+    let key = format!("{}:{}", CONSENT_KEY_REDIS_PREFIX, consent_id);
+    redis_service::redis_set(redis_pool, key, pending, CONSENT_REQUEST_EXPIRATION_SECONDS).await?;
+
+    Ok(Authorization::ConsentRequired(consent_request))
+}
+
+/// Removes the pending authorization waiting for the user's consent,
+/// it can only be answered once and only by the account which started it.
+pub async fn take_pending_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    consent_id: &str,
+    account_id: Uuid,
+) -> Result<PendingAuthorization, Oauth2Error> {
+    let key = format!("{}:{}", CONSENT_KEY_REDIS_PREFIX, consent_id);
+    let pending: PendingAuthorization = redis_service::redis_get_option(redis_pool, key.clone())
         .await?
-        .ok_or(Oauth2Error::NoClientWithId)?;
+        .ok_or(Oauth2Error::InvalidConsentRequest)?;
+
+    redis_service::redis_del(redis_pool, key).await?;
 
-    if redirect_uri != client.redirect_uri {
+    if pending.account_id != account_id {
         error!(
-            "Redirect uri doesn't match, request redirect_uri: {}, client set redirect_uri: {}",
-            redirect_uri, client.redirect_uri
+            "Consent request for account {} was answered by account {}",
+            pending.account_id, account_id
         );
-        return Err(Oauth2Error::InvalidRedirectUri);
+        return Err(Oauth2Error::InvalidConsentRequest);
     }
 
+    Ok(pending)
+}
+
+/// Remembers the user's consent and returns the uri redirecting them back to the client.
+pub async fn approve_authorization(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    pending: PendingAuthorization,
+) -> Result<String, Oauth2Error> {
+    consent_service::grant_consent(
+        db_pool,
+        pending.account_id,
+        pending.client_id.clone(),
+        &pending.scopes,
+    )
+    .await?;
+
+    create_authorization_code(redis_pool, pending).await
+}
+
+async fn create_authorization_code(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    pending: PendingAuthorization,
+) -> Result<String, Oauth2Error> {
     let code: String = thread_rng()
         .sample_iter(&Alphanumeric)
         .take(AUTH_TOKEN_LENGTH)
@@ -85,10 +307,28 @@ pub async fn get_auth_token(
 
     let auth_token = AuthToken {
         code: code.clone(),
-        client_id,
-        account_id,
+        client_id: pending.client_id,
+        account_id: pending.account_id,
+        redirect_uri: pending.redirect_uri.clone(),
+        scopes: pending.scopes,
+        auth_time: pending.auth_time,
+        code_challenge: pending.code_challenge,
+        nonce: pending.nonce,
+        sid: pending
+            .session_id
+            .as_deref()
+            .map(backchannel_logout_service::session_sid),
     };
 
+    if let Some(session_id) = &pending.session_id {
+        backchannel_logout_service::register_session_client(
+            redis_pool,
+            session_id,
+            &auth_token.client_id,
+        )
+        .await?;
+    }
+
     let key = format!("{}:{}", AUTHORIZATION_KEY_REDIS_PREFIX, code);
     redis_service::redis_set(
         redis_pool,
@@ -98,110 +338,887 @@ pub async fn get_auth_token(
     )
     .await?;
 
-    transaction.commit().await?;
-
+    let separator = if pending.redirect_uri.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
     Ok(format!(
-        "{}?state={}&code={}",
-        client.redirect_uri, state, code
+        "{}{}state={}&code={}",
+        pending.redirect_uri,
+        separator,
+        RawStr::new(&pending.state).percent_encode(),
+        code
     ))
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+struct Reauthentication {
+    client_id: String,
+    started_at: DateTime<Utc>,
+}
+
+/// Starts a required login for an authorization request (prompt=login or max_age=0),
+/// the returned id is carried along with the request through the login.
+pub async fn start_reauthentication(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+) -> Result<String, Oauth2Error> {
+    let reauthentication_id: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REAUTHENTICATION_ID_LENGTH)
+        .map(char::from)
+        .collect();
+    let reauthentication = Reauthentication {
+        client_id,
+        started_at: Utc::now(),
+    };
+
+    let key = format!(
+        "{}:{}",
+        REAUTHENTICATION_KEY_REDIS_PREFIX, reauthentication_id
+    );
+    redis_service::redis_set(
+        redis_pool,
+        key,
+        reauthentication,
+        REAUTHENTICATION_EXPIRATION_SECONDS,
+    )
+    .await?;
+
+    Ok(reauthentication_id)
+}
+
+/// Returns when the client's reauthentication was started, only a login after that satisfies it.
+/// Each reauthentication can only be used once.
+pub async fn take_reauthentication(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: &str,
+    reauthentication_id: &str,
+) -> Result<Option<DateTime<Utc>>, Oauth2Error> {
+    let key = format!(
+        "{}:{}",
+        REAUTHENTICATION_KEY_REDIS_PREFIX, reauthentication_id
+    );
+    let reauthentication: Option<Reauthentication> =
+        redis_service::redis_get_del(redis_pool, key).await?;
+
+    Ok(reauthentication
+        .filter(|reauthentication| reauthentication.client_id == client_id)
+        .map(|reauthentication| reauthentication.started_at))
+}
+
+fn generate_consent_id() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CONSENT_ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Looks up the client and checks that the redirect uri is registered for it.
+/// Until this succeeds, authorization errors must not be sent back to the redirect uri.
+pub async fn get_client_for_redirect_uri(
+    db_pool: &State<Pool<DB>>,
+    client_id: &str,
+    redirect_uri: &str,
+) -> Result<OauthClient, Oauth2Error> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let client = oauth_client_repository::get_by_client_id(&mut transaction, client_id)
+        .await?
+        .ok_or(Oauth2Error::NoClientWithId)?;
+
+    transaction.commit().await?;
+
+    if !redirect_uri::matches_registered(&client.redirect_uris, redirect_uri) {
+        error!(
+            "Redirect uri doesn't match, request redirect_uri: {}, client set redirect_uris: {:?}",
+            redirect_uri, client.redirect_uris
+        );
+        return Err(Oauth2Error::InvalidRedirectUri);
+    }
+
+    Ok(client)
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AccessToken {
     pub access_token: String,
     pub expiration: DateTime<Utc>,
+    // Tokens issued before this field was added expire within the hour, so approximating is fine.
+    #[serde(default = "Utc::now")]
+    pub issued_at: DateTime<Utc>,
     pub client_id: String,
-    pub account_id: Uuid,
+    /// Tokens issued through the client credentials grant act on behalf of the client itself
+    /// and are not tied to any account.
+    pub account_id: Option<Uuid>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// The service an exchanged token is restricted to, other tokens are intended for the client itself.
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// The clients which have exchanged the token on behalf of the account, the most recent first.
+    #[serde(default)]
+    pub act: Option<Actor>,
 }
 
+/// The actor claim (RFC 8693 section 4.1), each exchange nests the previous actors inside the new one.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Actor {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+/// What a token exchanged for another service is restricted to, on top of its scopes.
+struct TokenExchange {
+    audience: String,
+    act: Actor,
+    // The exchanged token can't outlive the token it was exchanged for.
+    expiration: DateTime<Utc>,
+}
+
+/// The tokens issued to a client by the token endpoint.
+#[derive(Debug, Clone)]
+pub struct IssuedTokens {
+    pub access_token: AccessToken,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+}
+
+impl From<AccessToken> for IssuedTokens {
+    fn from(access_token: AccessToken) -> Self {
+        IssuedTokens {
+            access_token,
+            refresh_token: None,
+            id_token: None,
+        }
+    }
+}
+
+/// Exchanges an authorization code for tokens.
+/// The client secret may only be omitted by public clients.
+#[allow(clippy::too_many_arguments)]
 pub async fn get_access_token(
     db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
     client_id: String,
-    client_secret: String,
+    client_secret: Option<String>,
     redirect_uri: String,
     code: String,
-) -> Result<AccessToken, Oauth2Error> {
+    code_verifier: Option<String>,
+) -> Result<IssuedTokens, Oauth2Error> {
+    let client = authenticate_client(db_pool, &client_id, client_secret.as_deref()).await?;
+
     let mut transaction = new_transaction(db_pool).await?;
 
-    let client = oauth_client_repository::get_by_client_id(&mut transaction, &client_id)
+    // The code is removed as it is read, so it can only be redeemed once
+    // even if it is sent in concurrent requests, and not again after a failed attempt.
+    let key = format!("{}:{}", AUTHORIZATION_KEY_REDIS_PREFIX, code);
+    let code_auth_token: AuthToken = redis_service::redis_get_del(redis_pool, key)
         .await?
-        .ok_or(Oauth2Error::NoClientWithId)?;
+        .ok_or(Oauth2Error::InvalidCode)?;
 
-    if client.redirect_uri != redirect_uri {
+    if code_auth_token.client_id != client_id {
         error!(
-            "Received redirect_uri ({}) did not match stored redirect_uri ({}) for client {}",
-            redirect_uri, client.redirect_uri, client_id
+            "Stored clientId for code(code={}) {} did not match provided client id {}",
+            code, code_auth_token.client_id, client_id
         );
-        return Err(Oauth2Error::InvalidRedirectUri);
+        return Err(Oauth2Error::InvalidClientId);
     }
 
-    if client.client_secret != client_secret {
+    if code_auth_token.redirect_uri != redirect_uri {
         error!(
-            "Received client_secret did not match stored client_secret for client {}",
-            client_id
+            "Received redirect_uri ({}) did not match the redirect_uri ({}) used for code {}",
+            redirect_uri, code_auth_token.redirect_uri, code
         );
-        return Err(Oauth2Error::InvalidClientSecret);
+        return Err(Oauth2Error::InvalidRedirectUri);
     }
 
-    let key = format!("{}:{}", AUTHORIZATION_KEY_REDIS_PREFIX, code);
-    let code_auth_token: AuthToken = redis_service::redis_get_option(redis_pool, key.clone())
-        .await?
-        .ok_or(Oauth2Error::InvalidCode)?;
+    if let Some(code_challenge) = &code_auth_token.code_challenge {
+        let verified = code_verifier
+            .as_deref()
+            .is_some_and(|code_verifier| code_challenge.verify(code_verifier));
 
-    if code_auth_token.client_id != client_id {
-        error!(
-            "Stored clientId for code(code={}) {} did not match provided client id {}",
-            code, code_auth_token.client_id, client_id
-        );
-        return Err(Oauth2Error::NoClientWithId);
+        if !verified {
+            error!(
+                "Code verifier did not match the code challenge for client {}",
+                client_id
+            );
+            return Err(Oauth2Error::InvalidCodeVerifier);
+        }
     }
 
     // The request has met the requirements, we can now issue the access token.
-    // Start by deleting it from the cache
-    redis_service::redis_del(redis_pool, key).await?;
 
     let access_token = generate_access_token(
+        db_pool,
         redis_pool,
-        code_auth_token.client_id,
+        config,
+        signing_keys,
+        &client,
+        Some(code_auth_token.account_id),
+        code_auth_token.scopes.clone(),
+        None,
+    )
+    .await?;
+
+    let refresh_token = refresh_token_service::create_refresh_token(
+        &mut transaction,
+        None,
         code_auth_token.account_id,
+        code_auth_token.client_id.clone(),
+        code_auth_token.scopes.clone(),
+        code_auth_token.auth_time,
+        false,
     )
     .await?;
 
     transaction.commit().await?;
 
-    Ok(access_token)
+    let id_token = create_id_token_if_requested(
+        db_pool,
+        config,
+        signing_keys,
+        &access_token,
+        code_auth_token.account_id,
+        code_auth_token.auth_time,
+        code_auth_token.nonce,
+        code_auth_token.sid,
+    )
+    .await?;
+
+    Ok(IssuedTokens {
+        access_token,
+        refresh_token: Some(refresh_token),
+        id_token,
+    })
 }
 
-pub async fn get_access_token_basic_auth(
+/// Exchanges a refresh token for a new access token, the refresh token is replaced by a new one.
+/// The client may request a subset of the scopes that were originally granted.
+#[allow(clippy::too_many_arguments)]
+pub async fn refresh_access_token(
+    db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
-    service: String,
-    account_id: Uuid,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    client_id: String,
+    client_secret: Option<String>,
+    refresh_token: String,
+    scopes: Option<Vec<String>>,
+) -> Result<IssuedTokens, Oauth2Error> {
+    let client = authenticate_client(db_pool, &client_id, client_secret.as_deref()).await?;
+
+    let (used_token, new_refresh_token) = refresh_token_service::rotate_refresh_token(
+        db_pool,
+        &client_id,
+        &refresh_token,
+        scopes.as_deref(),
+        false,
+    )
+    .await?;
+
+    let access_token = generate_access_token(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &client,
+        Some(used_token.account_id),
+        scopes.unwrap_or(used_token.scopes),
+        None,
+    )
+    .await?;
+
+    let id_token = create_id_token_if_requested(
+        db_pool,
+        config,
+        signing_keys,
+        &access_token,
+        used_token.account_id,
+        used_token.auth_time,
+        // The nonce belongs to the authorization request, it isn't repeated on refresh.
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(IssuedTokens {
+        access_token,
+        refresh_token: Some(new_refresh_token),
+        id_token,
+    })
+}
+
+/// Stores the authorization parameters pushed by the client (RFC 9126),
+/// they are validated up front so that the client learns about errors before involving the user.
+pub async fn push_authorization_request(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    client_secret: Option<String>,
+    parameters: AuthorizationParameters,
+) -> Result<PushedAuthorization, Oauth2Error> {
+    let client = authenticate_client(db_pool, &client_id, client_secret.as_deref()).await?;
+
+    if !redirect_uri::matches_registered(&client.redirect_uris, &parameters.redirect_uri) {
+        error!(
+            "Pushed redirect uri doesn't match, redirect_uri: {}, client set redirect_uris: {:?}",
+            parameters.redirect_uri, client.redirect_uris
+        );
+        return Err(Oauth2Error::InvalidRedirectUri);
+    }
+
+    resolve_scopes(
+        &client,
+        parameters.scope.as_deref().map(scope::parse_scopes),
+    )
+    .ok_or(Oauth2Error::InvalidScope)?;
+
+    if (client.require_pkce || client.public_client) && parameters.code_challenge.is_none() {
+        return Err(Oauth2Error::CodeChallengeRequired);
+    }
+
+    let pushed = pushed_authorization_service::create_pushed_authorization(
+        redis_pool, client_id, parameters,
+    )
+    .await?;
+
+    Ok(pushed)
+}
+
+/// Starts the device authorization grant (RFC 8628) for clients that can't receive redirects.
+pub async fn start_device_authorization(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    client_secret: Option<String>,
+    scopes: Option<Vec<String>>,
+) -> Result<DeviceAuthorization, Oauth2Error> {
+    let client = authenticate_client(db_pool, &client_id, client_secret.as_deref()).await?;
+
+    let scopes = resolve_scopes(&client, scopes).ok_or(Oauth2Error::InvalidScope)?;
+
+    let device_authorization =
+        device_authorization_service::create_device_authorization(redis_pool, client_id, scopes)
+            .await?;
+
+    Ok(device_authorization)
+}
+
+/// Looks up the device authorization behind the code the user entered.
+pub async fn get_device_verification(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    user_code: &str,
+) -> Result<DeviceVerification, Oauth2Error> {
+    let device_authorization =
+        device_authorization_service::get_by_user_code(redis_pool, user_code).await?;
+
+    if !matches!(
+        device_authorization.status,
+        DeviceAuthorizationStatus::Pending
+    ) {
+        return Err(DeviceAuthorizationError::InvalidUserCode.into());
+    }
+
+    let client = get_device_client(db_pool, &device_authorization).await?;
+
+    Ok(DeviceVerification {
+        user_code: device_authorization_service::format_user_code(&device_authorization.user_code),
+        client_name: client.client_name,
+        scopes: device_authorization.scopes,
+    })
+}
+
+/// Records the user's decision for the device,
+/// approving it also remembers the consent to the scopes like the authorization endpoint does.
+pub async fn complete_device_verification(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    user_code: &str,
+    session: &Session,
+    approved: bool,
+) -> Result<(), Oauth2Error> {
+    let device_authorization =
+        device_authorization_service::get_by_user_code(redis_pool, user_code).await?;
+
+    if approved {
+        let client = get_device_client(db_pool, &device_authorization).await?;
+
+        if !client.first_party {
+            consent_service::grant_consent(
+                db_pool,
+                session.account_id,
+                client.client_id,
+                &device_authorization.scopes,
+            )
+            .await?;
+        }
+    }
+
+    device_authorization_service::complete_device_authorization(
+        redis_pool,
+        device_authorization,
+        session.account_id,
+        session.authenticated_at,
+        approved,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The client may have been removed since it started the device authorization.
+async fn get_device_client(
+    db_pool: &State<Pool<DB>>,
+    device_authorization: &DeviceAuthorization,
+) -> Result<OauthClient, Oauth2Error> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let client = oauth_client_repository::get_by_client_id(
+        &mut transaction,
+        &device_authorization.client_id,
+    )
+    .await?
+    .ok_or(DeviceAuthorizationError::InvalidUserCode)?;
+    transaction.commit().await?;
+
+    Ok(client)
+}
+
+/// Exchanges a device code for tokens once the user has approved the device.
+pub async fn get_device_code_token(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    client_id: String,
+    client_secret: Option<String>,
+    device_code: String,
+) -> Result<IssuedTokens, Oauth2Error> {
+    let client = authenticate_client(db_pool, &client_id, client_secret.as_deref()).await?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let device_authorization = device_authorization_service::poll_device_authorization(
+        redis_pool,
+        &client_id,
+        &device_code,
+    )
+    .await?;
+
+    let DeviceAuthorizationStatus::Approved {
+        account_id,
+        auth_time,
+    } = device_authorization.status
+    else {
+        return Err(DeviceAuthorizationError::AuthorizationPending.into());
+    };
+
+    let access_token = generate_access_token(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &client,
+        Some(account_id),
+        device_authorization.scopes.clone(),
+        None,
+    )
+    .await?;
+
+    let refresh_token = refresh_token_service::create_refresh_token(
+        &mut transaction,
+        None,
+        account_id,
+        client_id,
+        device_authorization.scopes,
+        auth_time,
+        false,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    let id_token = create_id_token_if_requested(
+        db_pool,
+        config,
+        signing_keys,
+        &access_token,
+        account_id,
+        auth_time,
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(IssuedTokens {
+        access_token,
+        refresh_token: Some(refresh_token),
+        id_token,
+    })
+}
+
+/// Issues an access token to the client itself, rather than to an account (RFC 6749 section 4.4).
+/// Only confidential clients may use this grant.
+pub async fn get_client_credentials_token(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    client_id: String,
+    client_secret: String,
+    scopes: Option<Vec<String>>,
 ) -> Result<AccessToken, Oauth2Error> {
-    generate_access_token(redis_pool, service, account_id).await
+    let client = authenticate_client(db_pool, &client_id, Some(&client_secret)).await?;
+
+    let scopes = scopes.unwrap_or_default();
+    // There is no user to identify, the OpenID Connect scopes make no sense here.
+    if let Some(scope) = scopes.iter().find(|scope| {
+        !scope::has_scope(&client.allowed_scopes, scope)
+            || STANDARD_SCOPES.contains(&scope.as_str())
+    }) {
+        error!(
+            "Client {} requested scope '{}' which is not allowed for client credentials",
+            client_id, scope
+        );
+        return Err(Oauth2Error::InvalidScope);
+    }
+
+    generate_access_token(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &client,
+        None,
+        scopes,
+        None,
+    )
+    .await
 }
 
-async fn generate_access_token(
+/// Exchanges an account's access token for one that the client can use to call the audience on the
+/// account's behalf (RFC 8693). The client's policy for the audience limits the scopes of the new token,
+/// and the client is added to the token's actors.
+#[allow(clippy::too_many_arguments)]
+pub async fn exchange_token(
+    db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
     client_id: String,
+    client_secret: String,
+    subject_token: String,
+    audience: String,
+    scopes: Option<Vec<String>>,
+) -> Result<AccessToken, Oauth2Error> {
+    let client = authenticate_client(db_pool, &client_id, Some(&client_secret)).await?;
+
+    let subject = find_access_token(db_pool, redis_pool, config, signing_keys, &subject_token)
+        .await?
+        .ok_or(Oauth2Error::InvalidSubjectToken)?;
+
+    // Client credentials tokens have no account that the client could act on behalf of.
+    let Some(account_id) = subject.account_id else {
+        return Err(Oauth2Error::InvalidSubjectToken);
+    };
+
+    let scopes = token_exchange_service::resolve_exchange_scopes(
+        db_pool,
+        &client.client_id,
+        &audience,
+        &subject.scopes,
+        scopes,
+    )
+    .await?;
+
+    let exchange = TokenExchange {
+        audience,
+        act: Actor {
+            sub: client.client_id.clone(),
+            act: subject.act.map(Box::new),
+        },
+        expiration: subject.expiration,
+    };
+
+    generate_access_token(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &client,
+        Some(account_id),
+        scopes,
+        Some(exchange),
+    )
+    .await
+}
+
+pub const TOKEN_TYPE_HINT_REFRESH_TOKEN: &str = "refresh_token";
+
+/// Revokes an access or refresh token issued to the client (RFC 7009).
+/// Revoking a refresh token revokes every refresh token in its family.
+/// Tokens which are invalid or belong to another client are ignored.
+pub async fn revoke_token(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    client_secret: Option<String>,
+    token: String,
+    token_type_hint: Option<String>,
+) -> Result<(), Oauth2Error> {
+    authenticate_client(db_pool, &client_id, client_secret.as_deref()).await?;
+
+    // The hint only decides which kind of token is looked up first.
+    if token_type_hint.as_deref() == Some(TOKEN_TYPE_HINT_REFRESH_TOKEN) {
+        if refresh_token_service::revoke_refresh_token(db_pool, &client_id, &token).await? {
+            return Ok(());
+        }
+        revoke_access_token(redis_pool, &client_id, &token).await?;
+    } else if !revoke_access_token(redis_pool, &client_id, &token).await? {
+        refresh_token_service::revoke_refresh_token(db_pool, &client_id, &token).await?;
+    }
+
+    Ok(())
+}
+
+/// Looks up an access token on behalf of a resource server (RFC 7662).
+/// Returns `None` if the token isn't active.
+pub async fn introspect_token(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    client_id: String,
+    client_secret: String,
+    token: String,
+) -> Result<Option<AccessToken>, Oauth2Error> {
+    authenticate_client(db_pool, &client_id, Some(&client_secret)).await?;
+
+    find_access_token(db_pool, redis_pool, config, signing_keys, &token).await
+}
+
+/// Resolves an access token in either format, returns `None` if the token isn't active.
+/// JWT access tokens are verified rather than looked up, so they can't be revoked before they expire.
+pub async fn find_access_token(
+    db_pool: &Pool<DB>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    token: &str,
+) -> Result<Option<AccessToken>, Oauth2Error> {
+    if jwt_access_token_service::is_jwt(token) {
+        return match jwt_access_token_service::verify_jwt_access_token(
+            db_pool,
+            config,
+            signing_keys,
+            token,
+        )
+        .await
+        {
+            Ok(access_token) => Ok(Some(access_token)),
+            Err(err @ (JwtAccessTokenError::InvalidToken | JwtAccessTokenError::JwtError(_))) => {
+                info!("Rejected JWT access token, err: {}", err);
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        };
+    }
+
+    let key = format!("{}:{}", ACCESS_TOKEN_KEY_REDIS_PREFIX, token);
+    let access_token = redis_service::redis_get_option::<AccessToken>(redis_pool, key)
+        .await?
+        .filter(|access_token| access_token.expiration > Utc::now());
+
+    Ok(access_token)
+}
+
+/// Returns false if the token isn't a valid access token issued to the client.
+async fn revoke_access_token(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: &str,
+    token: &str,
+) -> Result<bool, Oauth2Error> {
+    let key = format!("{}:{}", ACCESS_TOKEN_KEY_REDIS_PREFIX, token);
+    let Some(access_token) =
+        redis_service::redis_get_option::<AccessToken>(redis_pool, key.clone()).await?
+    else {
+        return Ok(false);
+    };
+
+    if access_token.client_id != client_id {
+        error!(
+            "Client {} tried to revoke an access token issued to client {}",
+            client_id, access_token.client_id
+        );
+        return Ok(false);
+    }
+
+    redis_service::redis_del(redis_pool, key).await?;
+
+    Ok(true)
+}
+
+/// Checks that the requested scopes are allowed for the client,
+/// if the client doesn't request any scopes it is granted all of the scopes it is allowed.
+fn resolve_scopes(client: &OauthClient, scopes: Option<Vec<String>>) -> Option<Vec<String>> {
+    let Some(scopes) = scopes else {
+        return Some(client.allowed_scopes.clone());
+    };
+
+    if let Some(scope) = scopes
+        .iter()
+        .find(|scope| !scope::has_scope(&client.allowed_scopes, scope))
+    {
+        error!(
+            "Client {} requested scope '{}' which it is not allowed",
+            client.client_id, scope
+        );
+        return None;
+    }
+
+    Some(scopes)
+}
+
+/// Looks up the client and checks its secret,
+/// the secret may only be omitted by public clients (which are required to use PKCE).
+async fn authenticate_client(
+    db_pool: &State<Pool<DB>>,
+    client_id: &str,
+    client_secret: Option<&str>,
+) -> Result<OauthClient, Oauth2Error> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let client = oauth_client_repository::get_by_client_id(&mut transaction, client_id)
+        .await?
+        .ok_or(Oauth2Error::NoClientWithId)?;
+
+    let authenticated = match client_secret {
+        Some(client_secret) => {
+            oauth_client_service::verify_client_secret(&mut transaction, &client, client_secret)
+                .await?
+        }
+        None => client.public_client,
+    };
+
+    transaction.commit().await?;
+
+    if !authenticated {
+        error!(
+            "Missing or invalid client_secret for client {}",
+            client.client_id
+        );
+        return Err(Oauth2Error::InvalidClientSecret);
+    }
+
+    Ok(client)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn create_id_token_if_requested(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    access_token: &AccessToken,
     account_id: Uuid,
+    auth_time: DateTime<Utc>,
+    nonce: Option<String>,
+    sid: Option<String>,
+) -> Result<Option<String>, Oauth2Error> {
+    if !scope::has_scope(&access_token.scopes, SCOPE_OPENID) {
+        return Ok(None);
+    }
+
+    let id_token = id_token_service::create_id_token(
+        db_pool,
+        config,
+        signing_keys,
+        access_token.client_id.clone(),
+        account_id,
+        auth_time,
+        access_token.expiration,
+        &access_token.scopes,
+        nonce,
+        sid,
+    )
+    .await?;
+
+    Ok(Some(id_token))
+}
+
+/// Issues an opaque access token stored in redis,
+/// or a signed JWT which isn't stored anywhere if the client uses JWT access tokens.
+#[allow(clippy::too_many_arguments)]
+async fn generate_access_token(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    client: &OauthClient,
+    account_id: Option<Uuid>,
+    scopes: Vec<String>,
+    exchange: Option<TokenExchange>,
 ) -> Result<AccessToken, Oauth2Error> {
+    let issued_at = Utc::now();
+    let mut expiration_time: DateTime<Utc> = issued_at
+        .checked_add_signed(Duration::seconds(ACCESS_TOKEN_EXPIRATION_SECONDS))
+        .ok_or(Oauth2Error::ExpirationTimeGeneration)?;
+
+    let (audience, act) = match exchange {
+        Some(exchange) => {
+            expiration_time = expiration_time.min(exchange.expiration);
+            (Some(exchange.audience), Some(exchange.act))
+        }
+        None => (None, None),
+    };
+
+    if client.jwt_access_tokens {
+        let access_token = jwt_access_token_service::create_jwt_access_token(
+            db_pool,
+            config,
+            signing_keys,
+            &client.client_id,
+            account_id,
+            &scopes,
+            issued_at,
+            expiration_time,
+            audience.as_deref(),
+            act.as_ref(),
+        )
+        .await?;
+
+        return Ok(AccessToken {
+            access_token,
+            expiration: expiration_time,
+            issued_at,
+            client_id: client.client_id.clone(),
+            account_id,
+            scopes,
+            audience,
+            act,
+        });
+    }
+
     let access_token: String = thread_rng()
         .sample_iter(&Alphanumeric)
         .take(ACCESS_TOKEN_LENGTH)
         .map(char::from)
         .collect();
 
-    let time_until_expiration = Duration::seconds(ACCESS_TOKEN_EXPIRATION_SECONDS);
-    let expiration_time: DateTime<Utc> = Utc::now()
-        .checked_add_signed(time_until_expiration)
-        .ok_or(Oauth2Error::ExpirationTimeGeneration)?;
-
     let access_token: AccessToken = AccessToken {
-        access_token: access_token.clone(),
+        access_token,
         expiration: expiration_time,
-        client_id,
+        issued_at,
+        client_id: client.client_id.clone(),
         account_id,
+        scopes,
+        audience,
+        act,
     };
 
     let key = format!(
@@ -212,7 +1229,7 @@ async fn generate_access_token(
         redis_pool,
         key,
         access_token.clone(),
-        time_until_expiration.num_seconds() as usize,
+        (expiration_time - issued_at).num_seconds() as usize,
     )
     .await
     .or(Err(Oauth2Error::CacheInsertion))?;
@@ -1,13 +1,21 @@
+use chrono::{Duration, Utc};
+use openssl::{memcmp, sha::sha256};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use rocket::State;
-use sqlx::types::Uuid;
+use sqlx::{types::Uuid, Pool, Transaction};
 
 use crate::{
-    db::{new_transaction, oauth_client_repository, DB},
-    models::oauth_client::OauthClient,
-    util::accounts_error::AccountsError,
+    db::{new_transaction, oauth_client_repository, oauth_client_secret_repository, DB},
+    models::{oauth_client::OauthClient, oauth_client_secret::OauthClientSecret},
+    util::{
+        accounts_error::{AccountsError, AccountsResult},
+        redirect_uri,
+        scope::STANDARD_SCOPES,
+    },
 };
 
+use super::password_service::to_hex;
+
 const CLIENT_ID_LENGTH: usize = 32;
 const CLIENT_SECRET_LENGTH: usize = 128;
 
@@ -21,6 +29,38 @@ pub enum OauthClientError {
     InvalidId,
     #[error("No client with that ID exists")]
     ClientIdNotFound,
+    #[error("No secret with that ID exists for the client")]
+    SecretIdNotFound,
+    #[error("At least one redirect URI is required and all of them must be absolute URIs")]
+    InvalidRedirectUri,
+    #[error("The backchannel logout URI must be an https URI on a public host")]
+    InvalidBackchannelLogoutUri,
+    #[error("Registered clients can only be given the standard scopes")]
+    InvalidScope,
+    #[error("The registration access token is invalid")]
+    InvalidRegistrationToken,
+    #[error("The token endpoint auth method of a client can't be changed")]
+    AuthMethodChanged,
+}
+
+/// The metadata a client provides when registering itself (RFC 7591 section 2).
+#[derive(Debug, Clone)]
+pub struct ClientMetadata {
+    pub client_name: String,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Option<Vec<String>>,
+    /// Registered with the `none` token endpoint auth method, the client isn't given a secret.
+    pub public_client: bool,
+    pub post_logout_redirect_uris: Vec<String>,
+    pub backchannel_logout_uri: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisteredClient {
+    pub client: OauthClient,
+    pub client_secret: Option<String>,
+    /// Used by the client to manage its registration (RFC 7592), only returned once.
+    pub registration_token: String,
 }
 
 impl From<sqlx::Error> for OauthClientError {
@@ -49,15 +89,248 @@ pub async fn get_oauth_clients(
     }
 }
 
+/// The scopes clients can be given, the standard scopes followed by the custom scopes of every client.
+pub async fn get_supported_scopes(db_pool: &Pool<DB>) -> Result<Vec<String>, OauthClientError> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let clients = oauth_client_repository::get_all(&mut transaction).await?;
+    transaction.commit().await?;
+
+    let mut scopes: Vec<String> = STANDARD_SCOPES.iter().map(|s| s.to_string()).collect();
+    for scope in clients.into_iter().flat_map(|client| client.allowed_scopes) {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    Ok(scopes)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_oauth_client(
     db_pool: &State<sqlx::Pool<DB>>,
     client_name: String,
-    redirect_uri: String,
+    redirect_uris: Vec<String>,
+    allowed_scopes: Option<Vec<String>>,
+    require_pkce: bool,
+    public_client: bool,
+    first_party: bool,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+) -> Result<(OauthClient, Option<String>), OauthClientError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let (oauth_client, client_secret) = insert_client(
+        &mut transaction,
+        client_name,
+        redirect_uris,
+        allowed_scopes,
+        require_pkce,
+        public_client,
+        first_party,
+        post_logout_redirect_uris,
+        backchannel_logout_uri,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok((oauth_client, client_secret))
+}
+
+/// Registers a client on its own behalf, the caller has already presented the initial access token.
+/// Self registered clients are never first party and are limited to the standard scopes.
+pub async fn register_client(
+    db_pool: &State<sqlx::Pool<DB>>,
+    metadata: ClientMetadata,
+) -> Result<RegisteredClient, OauthClientError> {
+    validate_registered_scopes(metadata.scopes.as_deref())?;
+    validate_registered_backchannel_logout_uri(metadata.backchannel_logout_uri.as_deref())?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let (client, client_secret) = insert_client(
+        &mut transaction,
+        metadata.client_name,
+        metadata.redirect_uris,
+        metadata.scopes,
+        false,
+        metadata.public_client,
+        false,
+        metadata.post_logout_redirect_uris,
+        metadata.backchannel_logout_uri,
+    )
+    .await?;
+
+    let registration_token = generate_client_secret();
+    let client = oauth_client_repository::update_registration_token_hash(
+        &mut transaction,
+        client.id,
+        hash_client_secret(&registration_token),
+    )
+    .await?
+    .ok_or(OauthClientError::Internal)?;
+
+    transaction.commit().await?;
+
+    Ok(RegisteredClient {
+        client,
+        client_secret,
+        registration_token,
+    })
+}
+
+pub async fn get_registered_client(
+    db_pool: &State<sqlx::Pool<DB>>,
+    client_id: &str,
+    registration_token: &str,
+) -> Result<OauthClient, OauthClientError> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let client = get_by_registration_token(&mut transaction, client_id, registration_token).await?;
+    transaction.commit().await?;
+
+    Ok(client)
+}
+
+/// Replaces the client's metadata, values left out of the metadata are reset (RFC 7592 section 2.2).
+pub async fn update_registered_client(
+    db_pool: &State<sqlx::Pool<DB>>,
+    client_id: &str,
+    registration_token: &str,
+    metadata: ClientMetadata,
 ) -> Result<OauthClient, OauthClientError> {
+    validate_registered_scopes(metadata.scopes.as_deref())?;
+    validate_redirect_uris(&metadata.redirect_uris)?;
+    validate_optional_redirect_uris(&metadata.post_logout_redirect_uris)?;
+    validate_registered_backchannel_logout_uri(metadata.backchannel_logout_uri.as_deref())?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let client = get_by_registration_token(&mut transaction, client_id, registration_token).await?;
+
+    // A public client has no secret to switch to, and a confidential client shouldn't silently lose its secret.
+    if client.public_client != metadata.public_client {
+        return Err(OauthClientError::AuthMethodChanged);
+    }
+
+    if let Some(other) =
+        oauth_client_repository::get_by_client_name(&mut transaction, metadata.client_name.clone())
+            .await?
+    {
+        if other.id != client.id {
+            return Err(OauthClientError::ClientNameTaken);
+        }
+    }
+
+    let allowed_scopes = metadata
+        .scopes
+        .unwrap_or_else(|| STANDARD_SCOPES.iter().map(|s| s.to_string()).collect());
+
+    let client = oauth_client_repository::update_metadata(
+        &mut transaction,
+        client.id,
+        metadata.client_name,
+        metadata.redirect_uris,
+        allowed_scopes,
+        metadata.post_logout_redirect_uris,
+        metadata.backchannel_logout_uri,
+    )
+    .await?
+    .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(client)
+}
+
+pub async fn delete_registered_client(
+    db_pool: &State<sqlx::Pool<DB>>,
+    client_id: &str,
+    registration_token: &str,
+) -> Result<(), OauthClientError> {
     let mut transaction = new_transaction(db_pool).await?;
+    let client = get_by_registration_token(&mut transaction, client_id, registration_token).await?;
+
+    oauth_client_repository::delete_by_id(&mut transaction, client.id)
+        .await?
+        .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Clients which were created by an admin have no registration token and can't be managed this way.
+async fn get_by_registration_token(
+    transaction: &mut Transaction<'_, DB>,
+    client_id: &str,
+    registration_token: &str,
+) -> Result<OauthClient, OauthClientError> {
+    let client = oauth_client_repository::get_by_client_id(transaction, client_id)
+        .await?
+        .ok_or(OauthClientError::InvalidRegistrationToken)?;
+
+    let provided_hash = hash_client_secret(registration_token);
+    let is_valid = client.registration_token_hash.as_ref().is_some_and(|hash| {
+        hash.len() == provided_hash.len() && memcmp::eq(hash.as_bytes(), provided_hash.as_bytes())
+    });
+
+    if !is_valid {
+        return Err(OauthClientError::InvalidRegistrationToken);
+    }
+
+    Ok(client)
+}
+
+fn validate_registered_scopes(scopes: Option<&[String]>) -> Result<(), OauthClientError> {
+    if let Some(invalid) = scopes
+        .unwrap_or_default()
+        .iter()
+        .find(|scope| !STANDARD_SCOPES.contains(&scope.as_str()))
+    {
+        error!(
+            "Client tried to register the non-standard scope '{}'",
+            invalid
+        );
+        return Err(OauthClientError::InvalidScope);
+    }
+
+    Ok(())
+}
+
+/// Self registered clients aren't trusted, so we must not be made to send requests into our own network.
+fn validate_registered_backchannel_logout_uri(
+    backchannel_logout_uri: Option<&str>,
+) -> Result<(), OauthClientError> {
+    if let Some(uri) = backchannel_logout_uri {
+        if !redirect_uri::is_public_https_uri(uri) {
+            error!(
+                "Invalid backchannel logout uri '{}' for registered client",
+                uri
+            );
+            return Err(OauthClientError::InvalidBackchannelLogoutUri);
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn insert_client(
+    transaction: &mut Transaction<'_, DB>,
+    client_name: String,
+    redirect_uris: Vec<String>,
+    allowed_scopes: Option<Vec<String>>,
+    require_pkce: bool,
+    public_client: bool,
+    first_party: bool,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+) -> Result<(OauthClient, Option<String>), OauthClientError> {
+    validate_redirect_uris(&redirect_uris)?;
+    validate_optional_redirect_uris(&post_logout_redirect_uris)?;
+    validate_optional_redirect_uris(backchannel_logout_uri.as_slice())?;
 
     // Check if the name is taken already
-    match oauth_client_repository::get_by_client_name(&mut transaction, client_name.clone()).await {
+    match oauth_client_repository::get_by_client_name(transaction, client_name.clone()).await {
         Ok(None) => {}
         Ok(Some(_)) => return Err(OauthClientError::ClientNameTaken),
         Err(err) => {
@@ -72,34 +345,44 @@ pub async fn create_oauth_client(
         .map(char::from)
         .collect();
 
-    let client_secret: String = thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(CLIENT_SECRET_LENGTH)
-        .map(char::from)
-        .collect();
+    let allowed_scopes =
+        allowed_scopes.unwrap_or_else(|| STANDARD_SCOPES.iter().map(|s| s.to_string()).collect());
 
     let oauth_client = oauth_client_repository::insert(
-        &mut transaction,
+        transaction,
         client_id,
-        client_secret,
         client_name,
-        redirect_uri,
+        redirect_uris,
+        allowed_scopes,
+        require_pkce,
+        public_client,
+        first_party,
+        post_logout_redirect_uris,
+        backchannel_logout_uri,
     )
     .await?;
 
-    transaction.commit().await?;
+    // Public clients can't keep a secret, so they aren't given one.
+    if public_client {
+        return Ok((oauth_client, None));
+    }
 
-    Ok(oauth_client)
+    let client_secret = generate_client_secret();
+    oauth_client_secret_repository::insert(
+        transaction,
+        oauth_client.id,
+        hash_client_secret(&client_secret),
+    )
+    .await?;
+
+    Ok((oauth_client, Some(client_secret)))
 }
 
 pub async fn delete_oauth_client(
     db_pool: &State<sqlx::Pool<DB>>,
     id: String,
 ) -> Result<(), OauthClientError> {
-    let id = Uuid::parse_str(&id).map_err(|err| {
-        error!("Failed to parse oauth client id as UUID, err {}", err);
-        OauthClientError::InvalidId
-    })?;
+    let id = parse_id(&id)?;
 
     let mut transaction = new_transaction(db_pool).await?;
     oauth_client_repository::delete_by_id(&mut transaction, id)
@@ -114,3 +397,253 @@ pub async fn delete_oauth_client(
 
     Ok(())
 }
+
+pub async fn update_redirect_uris(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    redirect_uris: Vec<String>,
+) -> Result<OauthClient, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    validate_redirect_uris(&redirect_uris)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let oauth_client =
+        oauth_client_repository::update_redirect_uris(&mut transaction, id, redirect_uris)
+            .await?
+            .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(oauth_client)
+}
+
+pub async fn update_post_logout_redirect_uris(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    post_logout_redirect_uris: Vec<String>,
+) -> Result<OauthClient, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    validate_optional_redirect_uris(&post_logout_redirect_uris)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let oauth_client = oauth_client_repository::update_post_logout_redirect_uris(
+        &mut transaction,
+        id,
+        post_logout_redirect_uris,
+    )
+    .await?
+    .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(oauth_client)
+}
+
+/// The uri the client is notified at when a user's session ends, `None` disables the notifications.
+pub async fn update_backchannel_logout_uri(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    backchannel_logout_uri: Option<String>,
+) -> Result<OauthClient, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    validate_optional_redirect_uris(backchannel_logout_uri.as_slice())?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let oauth_client = oauth_client_repository::update_backchannel_logout_uri(
+        &mut transaction,
+        id,
+        backchannel_logout_uri,
+    )
+    .await?
+    .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(oauth_client)
+}
+
+/// First party clients skip the consent screen when users authorize them.
+pub async fn update_first_party(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    first_party: bool,
+) -> Result<OauthClient, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let oauth_client =
+        oauth_client_repository::update_first_party(&mut transaction, id, first_party)
+            .await?
+            .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(oauth_client)
+}
+
+/// Clients with JWT access tokens get signed tokens that resource servers can verify without asking us.
+pub async fn update_jwt_access_tokens(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    jwt_access_tokens: bool,
+) -> Result<OauthClient, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let oauth_client =
+        oauth_client_repository::update_jwt_access_tokens(&mut transaction, id, jwt_access_tokens)
+            .await?
+            .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(oauth_client)
+}
+
+/// Clients requiring pushed authorization requests can't be authorized with parameters in the url.
+pub async fn update_require_pushed_authorization_requests(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    require_pushed_authorization_requests: bool,
+) -> Result<OauthClient, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let oauth_client = oauth_client_repository::update_require_pushed_authorization_requests(
+        &mut transaction,
+        id,
+        require_pushed_authorization_requests,
+    )
+    .await?
+    .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(oauth_client)
+}
+
+fn validate_redirect_uris(redirect_uris: &[String]) -> Result<(), OauthClientError> {
+    if redirect_uris.is_empty() {
+        return Err(OauthClientError::InvalidRedirectUri);
+    }
+
+    validate_optional_redirect_uris(redirect_uris)
+}
+
+/// Checks every uri without requiring any, clients don't need post logout redirect uris.
+fn validate_optional_redirect_uris(redirect_uris: &[String]) -> Result<(), OauthClientError> {
+    if let Some(invalid) = redirect_uris
+        .iter()
+        .find(|uri| !redirect_uri::is_valid_redirect_uri(uri))
+    {
+        error!("Invalid redirect uri '{}'", invalid);
+        return Err(OauthClientError::InvalidRedirectUri);
+    }
+
+    Ok(())
+}
+
+pub async fn get_client_secrets(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+) -> Result<Vec<OauthClientSecret>, OauthClientError> {
+    let id = parse_id(&id)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let secrets = oauth_client_secret_repository::get_by_client(&mut transaction, id).await?;
+    transaction.commit().await?;
+
+    Ok(secrets)
+}
+
+/// Creates a new secret for the client.
+/// If an overlap is provided the client's other secrets expire after that many minutes,
+/// giving the client time to switch to the new secret.
+pub async fn create_client_secret(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    previous_secrets_expire_in_minutes: Option<i64>,
+) -> Result<(OauthClientSecret, String), OauthClientError> {
+    let id = parse_id(&id)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(OauthClientError::ClientIdNotFound)?;
+
+    if let Some(minutes) = previous_secrets_expire_in_minutes {
+        let expires_at = Utc::now() + Duration::minutes(minutes.max(0));
+        oauth_client_secret_repository::expire_all_by_client(&mut transaction, id, expires_at)
+            .await?;
+    }
+
+    let client_secret = generate_client_secret();
+    let secret = oauth_client_secret_repository::insert(
+        &mut transaction,
+        id,
+        hash_client_secret(&client_secret),
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok((secret, client_secret))
+}
+
+pub async fn delete_client_secret(
+    db_pool: &State<sqlx::Pool<DB>>,
+    id: String,
+    secret_id: String,
+) -> Result<(), OauthClientError> {
+    let id = parse_id(&id)?;
+    let secret_id = parse_id(&secret_id)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    oauth_client_secret_repository::delete(&mut transaction, id, secret_id)
+        .await?
+        .ok_or(OauthClientError::SecretIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Checks the secret against all of the client's active secrets in constant time.
+pub async fn verify_client_secret(
+    transaction: &mut Transaction<'_, DB>,
+    client: &OauthClient,
+    client_secret: &str,
+) -> AccountsResult<bool> {
+    let secrets =
+        oauth_client_secret_repository::get_active_by_client(transaction, client.id).await?;
+
+    let provided_hash = hash_client_secret(client_secret);
+    Ok(secrets.iter().any(|secret| {
+        secret.secret_hash.len() == provided_hash.len()
+            && memcmp::eq(secret.secret_hash.as_bytes(), provided_hash.as_bytes())
+    }))
+}
+
+fn generate_client_secret() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CLIENT_SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+// The secrets are long random strings, so a fast hash is enough to protect them at rest.
+fn hash_client_secret(client_secret: &str) -> String {
+    to_hex(&sha256(client_secret.as_bytes()))
+}
+
+fn parse_id(id: &str) -> Result<Uuid, OauthClientError> {
+    Uuid::parse_str(id).map_err(|err| {
+        error!("Failed to parse oauth client id as UUID, err {}", err);
+        OauthClientError::InvalidId
+    })
+}
@@ -128,14 +128,14 @@ pub fn verify_password(
         .is_ok()
 }
 
-fn to_hex(bytes: &[u8]) -> String {
+pub fn to_hex(bytes: &[u8]) -> String {
     bytes.iter().fold(String::new(), |mut output, b| {
         _ = write!(&mut output, "{b:02X}");
         output
     })
 }
 
-fn from_hex(hex_str: String) -> Result<Vec<u8>, ParseIntError> {
+pub fn from_hex(hex_str: String) -> Result<Vec<u8>, ParseIntError> {
     (0..hex_str.len())
         .step_by(2)
         .map(|i| u8::from_str_radix(&hex_str[i..i + 2], 16))
@@ -0,0 +1,146 @@
+use mobc_redis::RedisConnectionManager;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+
+use super::redis_service::{self, RedisError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PushedAuthorizationError {
+    #[error("The request uri is invalid or has expired")]
+    InvalidRequestUri,
+    #[error("Redis error")]
+    RedisError(#[from] RedisError),
+}
+
+// The request uri has to be a URN to tell it apart from a uri the request could be fetched from.
+const REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+const REQUEST_ID_LENGTH: usize = 48;
+const PUSHED_REQUEST_KEY_REDIS_PREFIX: &str = "pushed_authorization_requests";
+// 5 minutes, the request is kept until the user has logged in so it has to outlive the login.
+pub const PUSHED_REQUEST_EXPIRATION_SECONDS: usize = 5 * 60;
+
+/// The parameters of an authorization request (RFC 6749 section 4.1.1),
+/// which the client has sent directly to us rather than through the browser.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AuthorizationParameters {
+    pub response_type: String,
+    pub redirect_uri: String,
+    pub state: String,
+    pub scope: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub prompt: Option<String>,
+    pub max_age: Option<String>,
+    pub login_hint: Option<String>,
+    pub nonce: Option<String>,
+    pub reauthentication: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct PushedAuthorizationRequest {
+    client_id: String,
+    parameters: AuthorizationParameters,
+}
+
+#[derive(Debug)]
+pub struct PushedAuthorization {
+    pub request_uri: String,
+    pub expires_in: usize,
+}
+
+/// Stores the authorization parameters (RFC 9126), the client has already been authenticated
+/// and the parameters validated.
+pub async fn create_pushed_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    parameters: AuthorizationParameters,
+) -> Result<PushedAuthorization, PushedAuthorizationError> {
+    let request_uri = format!("{}{}", REQUEST_URI_PREFIX, generate_request_id());
+
+    save_pushed_authorization(redis_pool, client_id, &request_uri, parameters).await?;
+
+    Ok(PushedAuthorization {
+        request_uri,
+        expires_in: PUSHED_REQUEST_EXPIRATION_SECONDS,
+    })
+}
+
+/// Looks up the parameters behind the request uri,
+/// which may only be used by the client that pushed them.
+pub async fn get_pushed_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: &str,
+    request_uri: &str,
+) -> Result<AuthorizationParameters, PushedAuthorizationError> {
+    let key = format!("{}:{}", PUSHED_REQUEST_KEY_REDIS_PREFIX, request_uri);
+    let pushed: PushedAuthorizationRequest = redis_service::redis_get_option(redis_pool, key)
+        .await?
+        .ok_or(PushedAuthorizationError::InvalidRequestUri)?;
+
+    if pushed.client_id != client_id {
+        error!(
+            "Client {} tried to use a request uri pushed by client {}",
+            client_id, pushed.client_id
+        );
+        return Err(PushedAuthorizationError::InvalidRequestUri);
+    }
+
+    Ok(pushed.parameters)
+}
+
+/// Replaces the parameters behind the request uri, e.g. once the user has logged in.
+/// The request uri still expires when it was first going to.
+pub async fn update_pushed_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    request_uri: &str,
+    parameters: AuthorizationParameters,
+) -> Result<(), PushedAuthorizationError> {
+    let key = format!("{}:{}", PUSHED_REQUEST_KEY_REDIS_PREFIX, request_uri);
+    let pushed = PushedAuthorizationRequest {
+        client_id,
+        parameters,
+    };
+
+    if !redis_service::redis_replace(redis_pool, key, pushed).await? {
+        return Err(PushedAuthorizationError::InvalidRequestUri);
+    }
+
+    Ok(())
+}
+
+/// The request uri can only be used for a single authorization.
+pub async fn remove_pushed_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    request_uri: &str,
+) -> Result<(), PushedAuthorizationError> {
+    let key = format!("{}:{}", PUSHED_REQUEST_KEY_REDIS_PREFIX, request_uri);
+    redis_service::redis_del(redis_pool, key).await?;
+    Ok(())
+}
+
+async fn save_pushed_authorization(
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    client_id: String,
+    request_uri: &str,
+    parameters: AuthorizationParameters,
+) -> Result<(), PushedAuthorizationError> {
+    let key = format!("{}:{}", PUSHED_REQUEST_KEY_REDIS_PREFIX, request_uri);
+    let pushed = PushedAuthorizationRequest {
+        client_id,
+        parameters,
+    };
+
+    redis_service::redis_set(redis_pool, key, pushed, PUSHED_REQUEST_EXPIRATION_SECONDS).await?;
+
+    Ok(())
+}
+
+fn generate_request_id() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REQUEST_ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
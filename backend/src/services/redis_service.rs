@@ -1,5 +1,8 @@
 use mobc::Pool;
-use mobc_redis::{redis::AsyncCommands, RedisConnectionManager};
+use mobc_redis::{
+    redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions},
+    RedisConnectionManager,
+};
 use rocket::{serde::DeserializeOwned, State};
 use serde::Serialize;
 
@@ -63,6 +66,35 @@ where
         .map(Some)
 }
 
+/// Gets and deletes the value in a single command,
+/// so only one caller can ever receive it.
+pub async fn redis_get_del<T>(
+    redis_pool: &State<Pool<RedisConnectionManager>>,
+    key: String,
+) -> Result<Option<T>, RedisError>
+where
+    T: DeserializeOwned,
+{
+    let mut redis_conn = redis_pool.get().await.map_err(|err| {
+        error!("Failed to get redis connection from pool, err {}", err);
+        RedisError::Internal
+    })?;
+
+    let Some(raw_result) = redis_conn
+        .get_del::<String, Option<String>>(key)
+        .await
+        .inspect_err(|err| error!("Failed to get and delete from redis, err {}", err))
+        .map_err(|_| RedisError::Redis)?
+    else {
+        return Ok(None);
+    };
+
+    serde_json::from_str::<T>(&raw_result)
+        .inspect_err(|err| error!("Failed to parse json value from redis, err {}", err))
+        .map_err(|_| RedisError::Serde)
+        .map(Some)
+}
+
 pub async fn redis_set<T>(
     redis_pool: &State<Pool<RedisConnectionManager>>,
     key: String,
@@ -95,6 +127,43 @@ where
     Ok(())
 }
 
+/// Replaces the value of an existing key without changing when it expires,
+/// returns false if the key no longer exists.
+pub async fn redis_replace<T>(
+    redis_pool: &State<Pool<RedisConnectionManager>>,
+    key: String,
+    val: T,
+) -> Result<bool, RedisError>
+where
+    T: Serialize,
+{
+    let mut redis_conn = redis_pool.get().await.map_err(|err| {
+        error!("Failed to get redis connection from pool, err {}", err);
+        RedisError::Internal
+    })?;
+
+    let options = SetOptions::default()
+        .conditional_set(ExistenceCheck::XX)
+        .with_expiration(SetExpiry::KEEPTTL);
+
+    let result = redis_conn
+        .set_options::<String, String, Option<String>>(
+            key,
+            serde_json::to_string(&val).map_err(|err| {
+                error!("Failed to serialize value, err {}", err);
+                RedisError::Serde
+            })?,
+            options,
+        )
+        .await
+        .map_err(|err| {
+            error!("Failed to replace value in redis, err {}", err);
+            RedisError::Redis
+        })?;
+
+    Ok(result.is_some())
+}
+
 pub async fn redis_push(
     redis_pool: &State<Pool<RedisConnectionManager>>,
     key: String,
@@ -116,6 +185,45 @@ pub async fn redis_push(
     Ok(())
 }
 
+pub async fn redis_get_list(
+    redis_pool: &State<Pool<RedisConnectionManager>>,
+    key: String,
+) -> Result<Vec<String>, RedisError> {
+    let mut redis_conn = redis_pool.get().await.map_err(|err| {
+        error!("Failed to get redis connection from pool, err {}", err);
+        RedisError::Internal
+    })?;
+
+    redis_conn
+        .lrange::<String, Vec<String>>(key, 0, -1)
+        .await
+        .map_err(|err| {
+            error!("Failed to get list from redis, err {}", err);
+            RedisError::Redis
+        })
+}
+
+pub async fn redis_expire(
+    redis_pool: &State<Pool<RedisConnectionManager>>,
+    key: String,
+    expiration_seconds: usize,
+) -> Result<(), RedisError> {
+    let mut redis_conn = redis_pool.get().await.map_err(|err| {
+        error!("Failed to get redis connection from pool, err {}", err);
+        RedisError::Internal
+    })?;
+
+    redis_conn
+        .expire::<String, ()>(key, expiration_seconds)
+        .await
+        .map_err(|err| {
+            error!("Failed to set expiration in redis, err {}", err);
+            RedisError::Redis
+        })?;
+
+    Ok(())
+}
+
 pub async fn redis_del(
     redis_pool: &State<Pool<RedisConnectionManager>>,
     key: String,
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Duration, Utc};
+use openssl::sha::sha256;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rocket::State;
+use sqlx::{Pool, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    db::{new_transaction, refresh_token_repository, DB},
+    models::refresh_token::RefreshToken,
+    util::{accounts_error::AccountsError, scope},
+};
+
+use super::password_service::to_hex;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefreshTokenError {
+    #[error("Invalid refresh token provided")]
+    InvalidToken,
+    #[error("Refresh token has already been used, the token family has been revoked")]
+    TokenReused,
+    #[error("Requested scope was not granted to the refresh token")]
+    InvalidScope,
+    #[error("Failed to generate expiration time")]
+    ExpirationTimeGeneration,
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+const REFRESH_TOKEN_LENGTH: usize = 128;
+// 30 days
+const REFRESH_TOKEN_EXPIRATION_DAYS: i64 = 30;
+
+/// Creates a new refresh token, if no family is provided the token starts a new family.
+/// Registry refresh tokens are issued to docker logins for the registry with the service as the client id,
+/// they can only be used to get registry tokens and oauth refresh tokens can't be used for those.
+/// Returns the token, only its hash is stored.
+pub async fn create_refresh_token(
+    transaction: &mut Transaction<'_, DB>,
+    family_id: Option<Uuid>,
+    account_id: Uuid,
+    client_id: String,
+    scopes: Vec<String>,
+    auth_time: DateTime<Utc>,
+    registry: bool,
+) -> Result<String, RefreshTokenError> {
+    let token: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REFRESH_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+
+    let expires_at = Utc::now()
+        .checked_add_signed(Duration::days(REFRESH_TOKEN_EXPIRATION_DAYS))
+        .ok_or(RefreshTokenError::ExpirationTimeGeneration)?;
+
+    refresh_token_repository::insert(
+        transaction,
+        hash_token(&token),
+        family_id,
+        account_id,
+        client_id,
+        scopes,
+        auth_time,
+        expires_at,
+        registry,
+    )
+    .await?;
+
+    Ok(token)
+}
+
+/// Uses up the refresh token and replaces it with a new one in the same family.
+/// Presenting a token which has already been used revokes the entire family,
+/// as it means that the token has most likely been leaked.
+///
+/// Returns the used token together with its replacement.
+pub async fn rotate_refresh_token(
+    db_pool: &State<Pool<DB>>,
+    client_id: &str,
+    token: &str,
+    requested_scopes: Option<&[String]>,
+    registry: bool,
+) -> Result<(RefreshToken, String), RefreshTokenError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let refresh_token =
+        refresh_token_repository::get_by_token_hash(&mut transaction, &hash_token(token))
+            .await?
+            .ok_or(RefreshTokenError::InvalidToken)?;
+
+    if refresh_token.client_id != client_id {
+        error!(
+            "Refresh token issued to client {} was presented by client {}",
+            refresh_token.client_id, client_id
+        );
+        return Err(RefreshTokenError::InvalidToken);
+    }
+
+    if refresh_token.registry != registry {
+        error!(
+            "Refresh token for client {} was used for the wrong kind of token request",
+            client_id
+        );
+        return Err(RefreshTokenError::InvalidToken);
+    }
+
+    if refresh_token.revoked_at.is_some() || refresh_token.expires_at < Utc::now() {
+        return Err(RefreshTokenError::InvalidToken);
+    }
+
+    if let Some(requested_scopes) = requested_scopes {
+        if requested_scopes
+            .iter()
+            .any(|requested| !scope::has_scope(&refresh_token.scopes, requested))
+        {
+            return Err(RefreshTokenError::InvalidScope);
+        }
+    }
+
+    let Some(refresh_token) =
+        refresh_token_repository::mark_used(&mut transaction, refresh_token.id).await?
+    else {
+        warn!(
+            "Refresh token reuse detected for account {} and client {}, revoking token family {}",
+            refresh_token.account_id, refresh_token.client_id, refresh_token.family_id
+        );
+        refresh_token_repository::revoke_family(&mut transaction, refresh_token.family_id).await?;
+        transaction.commit().await?;
+        return Err(RefreshTokenError::TokenReused);
+    };
+
+    let new_token = create_refresh_token(
+        &mut transaction,
+        Some(refresh_token.family_id),
+        refresh_token.account_id,
+        refresh_token.client_id.clone(),
+        refresh_token.scopes.clone(),
+        refresh_token.auth_time,
+        refresh_token.registry,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok((refresh_token, new_token))
+}
+
+/// Revokes the family of the refresh token, returns false if the token isn't a valid oauth refresh token
+/// issued to the client.
+pub async fn revoke_refresh_token(
+    db_pool: &State<Pool<DB>>,
+    client_id: &str,
+    token: &str,
+) -> Result<bool, RefreshTokenError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let Some(refresh_token) =
+        refresh_token_repository::get_by_token_hash(&mut transaction, &hash_token(token)).await?
+    else {
+        return Ok(false);
+    };
+
+    if refresh_token.client_id != client_id || refresh_token.registry {
+        error!(
+            "Client {} tried to revoke a refresh token issued to client {}",
+            client_id, refresh_token.client_id
+        );
+        return Ok(false);
+    }
+
+    refresh_token_repository::revoke_family(&mut transaction, refresh_token.family_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(&sha256(token.as_bytes()))
+}
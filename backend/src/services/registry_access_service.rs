@@ -0,0 +1,618 @@
+use chrono::{DateTime, Duration, Utc};
+use jwt::{AlgorithmType, JoseHeader, SignWithKey, Token};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        account_repository, new_transaction, oauth_client_repository,
+        registry_access_rule_repository, DB,
+    },
+    models::{authority::AuthorityLevel, registry_access_rule::RegistryAccessRule},
+    util::{accounts_error::AccountsError, config::Config, uuid::uuid_to_sqlx},
+};
+
+use super::{
+    refresh_token_service::{self, RefreshTokenError},
+    signing_key_service::{self, ActiveSigningKey, SigningKeyCache, SigningKeyError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryAccessError {
+    #[error("No registry is registered for the service")]
+    InvalidService,
+    #[error("Account not found")]
+    AccountNotFound,
+    #[error("The provided ID is not a valid UUID")]
+    InvalidId,
+    #[error("No client with that ID exists")]
+    ClientIdNotFound,
+    #[error("No rule with that ID exists for the client")]
+    RuleIdNotFound,
+    #[error("A rule must apply to either an account or an authority level and grant at least one known action")]
+    InvalidRule,
+    #[error("Refresh token error")]
+    RefreshTokenError(#[from] RefreshTokenError),
+    #[error("JWT error")]
+    JwtError(#[from] jwt::Error),
+    #[error("Signing key error")]
+    SigningKeyError(#[from] SigningKeyError),
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+// The registry asks for a new token for every operation, so they can be short-lived.
+const REGISTRY_TOKEN_EXPIRATION_SECONDS: i64 = 5 * 60;
+const REGISTRY_TOKEN_ID_LENGTH: usize = 32;
+// Distinguishes registry tokens from other JWTs signed with the same key, such as ID tokens.
+const REGISTRY_TOKEN_TYPE: &str = "registry+jwt";
+
+const RESOURCE_TYPE_REPOSITORY: &str = "repository";
+const ACTION_WILDCARD: &str = "*";
+const REPOSITORY_ACTIONS: [&str; 4] = ["pull", "push", "delete", ACTION_WILDCARD];
+
+/// Access to a registry resource, requested as `type:name:action,action`
+/// (Docker token authentication specification).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegistryAccess {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub name: String,
+    pub actions: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct RegistryTokenClaims {
+    iss: String,
+    sub: String,
+    aud: String,
+    exp: i64,
+    nbf: i64,
+    iat: i64,
+    jti: String,
+    access: Vec<RegistryAccess>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryToken {
+    pub token: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration: DateTime<Utc>,
+    pub refresh_token: Option<String>,
+}
+
+/// Issues a token for the registry registered as a client with the service as its client id,
+/// the user has already been authenticated.
+/// Only the requested access that the account's rules allow is granted, the rest is left out.
+pub async fn get_registry_token(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    service: String,
+    account_id: Uuid,
+    scopes: &[String],
+    offline_token: bool,
+) -> Result<RegistryToken, RegistryAccessError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let access = resolve_access(&mut transaction, &service, account_id, scopes).await?;
+
+    // The refresh token isn't tied to any scope, the access is resolved again on every refresh.
+    let refresh_token = if offline_token {
+        Some(
+            refresh_token_service::create_refresh_token(
+                &mut transaction,
+                None,
+                account_id,
+                service.clone(),
+                vec![],
+                Utc::now(),
+                true,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    transaction.commit().await?;
+
+    let token =
+        create_registry_token(db_pool, config, signing_keys, service, account_id, access).await?;
+
+    Ok(RegistryToken {
+        refresh_token,
+        ..token
+    })
+}
+
+/// Issues a new token with a refresh token from an earlier offline token request,
+/// the refresh token is rotated.
+pub async fn refresh_registry_token(
+    db_pool: &State<Pool<DB>>,
+    config: &State<Config>,
+    signing_keys: &SigningKeyCache,
+    service: String,
+    refresh_token: &str,
+    scopes: &[String],
+) -> Result<RegistryToken, RegistryAccessError> {
+    let (used_token, new_refresh_token) =
+        refresh_token_service::rotate_refresh_token(db_pool, &service, refresh_token, None, true)
+            .await?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let access = resolve_access(&mut transaction, &service, used_token.account_id, scopes).await?;
+    transaction.commit().await?;
+
+    let token = create_registry_token(
+        db_pool,
+        config,
+        signing_keys,
+        service,
+        used_token.account_id,
+        access,
+    )
+    .await?;
+
+    Ok(RegistryToken {
+        refresh_token: Some(new_refresh_token),
+        ..token
+    })
+}
+
+/// Whether tokens can be requested for the service, i.e. whether it is a client with registry access rules.
+pub async fn is_registry(
+    db_pool: &State<Pool<DB>>,
+    service: &str,
+) -> Result<bool, RegistryAccessError> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let rules = registry_access_rule_repository::get_by_client(&mut transaction, service).await?;
+    transaction.commit().await?;
+
+    Ok(!rules.is_empty())
+}
+
+pub async fn get_rules(
+    db_pool: &State<Pool<DB>>,
+    id: String,
+) -> Result<Vec<RegistryAccessRule>, RegistryAccessError> {
+    let id = parse_id(&id).ok_or(RegistryAccessError::InvalidId)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let client = oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(RegistryAccessError::ClientIdNotFound)?;
+    let rules =
+        registry_access_rule_repository::get_by_client(&mut transaction, &client.client_id).await?;
+    transaction.commit().await?;
+
+    Ok(rules)
+}
+
+/// Adds a rule to the registry client, repositories ending with a `*` match every repository with that prefix.
+pub async fn create_rule(
+    db_pool: &State<Pool<DB>>,
+    id: String,
+    account_id: Option<String>,
+    authority: Option<AuthorityLevel>,
+    repository: String,
+    actions: Vec<String>,
+) -> Result<RegistryAccessRule, RegistryAccessError> {
+    let id = parse_id(&id).ok_or(RegistryAccessError::InvalidId)?;
+    let account_id = match account_id {
+        Some(account_id) => Some(parse_id(&account_id).ok_or(RegistryAccessError::InvalidId)?),
+        None => None,
+    };
+
+    if account_id.is_some() == authority.is_some()
+        || repository.is_empty()
+        || actions.is_empty()
+        || actions
+            .iter()
+            .any(|action| !REPOSITORY_ACTIONS.contains(&action.as_str()))
+    {
+        return Err(RegistryAccessError::InvalidRule);
+    }
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let client = oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(RegistryAccessError::ClientIdNotFound)?;
+
+    if let Some(account_id) = account_id {
+        account_repository::get_account(&mut transaction, account_id)
+            .await?
+            .ok_or(RegistryAccessError::AccountNotFound)?;
+    }
+
+    let rule = registry_access_rule_repository::insert(
+        &mut transaction,
+        client.client_id,
+        account_id,
+        authority,
+        repository,
+        actions,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(rule)
+}
+
+pub async fn delete_rule(
+    db_pool: &State<Pool<DB>>,
+    id: String,
+    rule_id: String,
+) -> Result<(), RegistryAccessError> {
+    let id = parse_id(&id).ok_or(RegistryAccessError::InvalidId)?;
+    let rule_id = parse_id(&rule_id).ok_or(RegistryAccessError::InvalidId)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let client = oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(RegistryAccessError::ClientIdNotFound)?;
+
+    registry_access_rule_repository::delete(&mut transaction, &client.client_id, rule_id)
+        .await?
+        .ok_or(RegistryAccessError::RuleIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn resolve_access(
+    transaction: &mut Transaction<'_, DB>,
+    service: &str,
+    account_id: Uuid,
+    scopes: &[String],
+) -> Result<Vec<RegistryAccess>, RegistryAccessError> {
+    oauth_client_repository::get_by_client_id(transaction, service)
+        .await?
+        .ok_or(RegistryAccessError::InvalidService)?;
+
+    let account = account_repository::get_account(transaction, uuid_to_sqlx(account_id))
+        .await?
+        .ok_or(RegistryAccessError::AccountNotFound)?;
+
+    let rules = registry_access_rule_repository::get_for_account(
+        transaction,
+        service,
+        account.id,
+        account.authority,
+    )
+    .await?;
+
+    Ok(scopes
+        .iter()
+        .flat_map(|scope| scope.split_whitespace())
+        .filter_map(parse_scope)
+        .filter_map(|requested| grant_access(&rules, requested))
+        .collect())
+}
+
+/// Parses a scope such as `repository:team/app:pull,push`,
+/// the name may itself contain colons, e.g. if it includes a registry host and port.
+fn parse_scope(scope: &str) -> Option<RegistryAccess> {
+    let (resource_type, rest) = scope.split_once(':')?;
+    let (name, actions) = rest.rsplit_once(':')?;
+
+    Some(RegistryAccess {
+        resource_type: resource_type.to_string(),
+        name: name.to_string(),
+        actions: actions
+            .split(',')
+            .filter(|action| !action.is_empty())
+            .map(|action| action.to_string())
+            .collect(),
+    })
+}
+
+/// Narrows the requested access down to the actions allowed by the rules,
+/// only repositories can be granted access to.
+fn grant_access(
+    rules: &[RegistryAccessRule],
+    mut requested: RegistryAccess,
+) -> Option<RegistryAccess> {
+    if requested.resource_type != RESOURCE_TYPE_REPOSITORY {
+        return None;
+    }
+
+    let matching_rules: Vec<&RegistryAccessRule> = rules
+        .iter()
+        .filter(|rule| matches_repository(&rule.repository, &requested.name))
+        .collect();
+
+    requested.actions.retain(|action| {
+        matching_rules.iter().any(|rule| {
+            rule.actions
+                .iter()
+                .any(|allowed| allowed == action || allowed == ACTION_WILDCARD)
+        })
+    });
+
+    (!requested.actions.is_empty()).then_some(requested)
+}
+
+fn matches_repository(rule: &str, repository: &str) -> bool {
+    match rule.strip_suffix('*') {
+        Some(prefix) => repository.starts_with(prefix),
+        None => rule == repository,
+    }
+}
+
+/// The token is signed with our current signing key and only identifies it by its key id,
+/// the JWK thumbprint, without an x5c certificate chain.
+/// Registries therefore have to be Docker distribution 3 or later with `auth.token.jwks`
+/// set to a copy of our published keys (`/api/openid/jwks.json`), kept up to date as the keys rotate.
+/// Distribution 2 only trusts key ids derived from its certificate bundle and can't verify the tokens.
+async fn create_registry_token(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    service: String,
+    account_id: Uuid,
+    access: Vec<RegistryAccess>,
+) -> Result<RegistryToken, RegistryAccessError> {
+    let issued_at = Utc::now();
+    let expiration = issued_at + Duration::seconds(REGISTRY_TOKEN_EXPIRATION_SECONDS);
+
+    let claims = RegistryTokenClaims {
+        iss: config.backend_address.clone(),
+        sub: account_id.to_string(),
+        aud: service,
+        exp: expiration.timestamp(),
+        nbf: issued_at.timestamp(),
+        iat: issued_at.timestamp(),
+        jti: generate_token_id(),
+        access,
+    };
+
+    let signing_key = signing_key_service::get_signing_key(db_pool, config, signing_keys).await?;
+    let token = sign_registry_token(claims, signing_key)?;
+
+    Ok(RegistryToken {
+        token,
+        issued_at,
+        expiration,
+        refresh_token: None,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RegistryTokenHeader {
+    alg: AlgorithmType,
+    kid: String,
+    typ: String,
+}
+
+impl JoseHeader for RegistryTokenHeader {
+    fn algorithm_type(&self) -> AlgorithmType {
+        self.alg
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        Some(&self.kid)
+    }
+}
+
+fn sign_registry_token(
+    claims: RegistryTokenClaims,
+    signing_key: ActiveSigningKey,
+) -> Result<String, jwt::Error> {
+    let header = RegistryTokenHeader {
+        alg: AlgorithmType::Rs256,
+        kid: signing_key.kid,
+        typ: REGISTRY_TOKEN_TYPE.to_string(),
+    };
+
+    let token = Token::new(header, claims).sign_with_key(&signing_key.key)?;
+
+    Ok(token.as_str().to_string())
+}
+
+fn generate_token_id() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REGISTRY_TOKEN_ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+fn parse_id(id: &str) -> Option<Uuid> {
+    Uuid::parse_str(id)
+        .map_err(|err| error!("Failed to parse id as UUID, err {}", err))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jwt::{Header, PKeyWithDigest, Unverified, VerifyWithKey};
+    use openssl::{hash::MessageDigest, pkey::PKey, rsa::Rsa};
+    use serde_json::Value;
+
+    use super::*;
+
+    fn claims() -> RegistryTokenClaims {
+        RegistryTokenClaims {
+            iss: String::from("https://accounts.example.com"),
+            sub: Uuid::nil().to_string(),
+            aud: String::from("registry.example.com"),
+            exp: 2,
+            nbf: 1,
+            iat: 1,
+            jti: generate_token_id(),
+            access: vec![],
+        }
+    }
+
+    fn rule(repository: &str, actions: &[&str]) -> RegistryAccessRule {
+        RegistryAccessRule {
+            id: Uuid::nil(),
+            client_id: String::from("registry.example.com"),
+            account_id: None,
+            authority: Some(AuthorityLevel::User),
+            repository: repository.to_string(),
+            actions: actions.iter().map(|action| action.to_string()).collect(),
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+        }
+    }
+
+    fn access(name: &str, actions: &[&str]) -> RegistryAccess {
+        RegistryAccess {
+            resource_type: String::from(RESOURCE_TYPE_REPOSITORY),
+            name: name.to_string(),
+            actions: actions.iter().map(|action| action.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_scope() {
+        assert_eq!(
+            parse_scope("repository:team/app:pull,push"),
+            Some(access("team/app", &["pull", "push"]))
+        );
+    }
+
+    #[test]
+    fn parses_scope_with_registry_host_and_port() {
+        assert_eq!(
+            parse_scope("repository:registry.example.com:5000/team/app:pull"),
+            Some(access("registry.example.com:5000/team/app", &["pull"]))
+        );
+    }
+
+    #[test]
+    fn parses_scope_without_actions() {
+        assert_eq!(
+            parse_scope("repository:team/app:"),
+            Some(access("team/app", &[]))
+        );
+        assert_eq!(parse_scope("repository"), None);
+        assert_eq!(parse_scope("repository:team/app"), None);
+    }
+
+    #[test]
+    fn grants_only_allowed_actions() {
+        let rules = [rule("team/app", &["pull"])];
+        assert_eq!(
+            grant_access(&rules, access("team/app", &["pull", "push"])),
+            Some(access("team/app", &["pull"]))
+        );
+    }
+
+    #[test]
+    fn combines_matching_rules() {
+        let rules = [rule("team/app", &["pull"]), rule("team/*", &["push"])];
+        assert_eq!(
+            grant_access(&rules, access("team/app", &["pull", "push", "delete"])),
+            Some(access("team/app", &["pull", "push"]))
+        );
+    }
+
+    #[test]
+    fn wildcard_action_allows_every_action() {
+        let rules = [rule("team/app", &[ACTION_WILDCARD])];
+        assert_eq!(
+            grant_access(&rules, access("team/app", &["pull", "delete"])),
+            Some(access("team/app", &["pull", "delete"]))
+        );
+    }
+
+    #[test]
+    fn grants_nothing_without_matching_rule() {
+        let rules = [rule("team/app", &["pull"])];
+        assert_eq!(grant_access(&rules, access("team/other", &["pull"])), None);
+        assert_eq!(grant_access(&rules, access("team/app", &["push"])), None);
+    }
+
+    #[test]
+    fn only_grants_repository_access() {
+        let rules = [rule("*", &[ACTION_WILDCARD])];
+        let mut registry = access("catalog", &["*"]);
+        registry.resource_type = String::from("registry");
+        assert_eq!(grant_access(&rules, registry), None);
+    }
+
+    #[test]
+    fn matches_repository_prefix() {
+        assert!(matches_repository("team/*", "team/app"));
+        assert!(matches_repository("team/*", "team/app/nested"));
+        assert!(matches_repository("*", "anything"));
+        assert!(!matches_repository("team/*", "other/app"));
+        assert!(!matches_repository("team/*", "team"));
+    }
+
+    #[test]
+    fn matches_repository_exactly_without_wildcard() {
+        assert!(matches_repository("team/app", "team/app"));
+        assert!(!matches_repository("team/app", "team/app2"));
+        assert!(!matches_repository("team/app", "team/app/nested"));
+        assert!(!matches_repository("team/app", "team"));
+    }
+
+    // The wildcard is only special at the end of the rule.
+    #[test]
+    fn matches_wildcard_in_middle_literally() {
+        assert!(!matches_repository("team/*/app", "team/x/app"));
+        assert!(matches_repository("team/*/app", "team/*/app"));
+    }
+
+    fn signing_key() -> (ActiveSigningKey, PKeyWithDigest<openssl::pkey::Public>) {
+        let rsa = Rsa::generate(2048).unwrap();
+        let kid = signing_key_service::jwk_thumbprint(
+            &URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+            &URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+        );
+        let public_key = PKeyWithDigest {
+            digest: MessageDigest::sha256(),
+            key: PKey::public_key_from_pem(&rsa.public_key_to_pem().unwrap()).unwrap(),
+        };
+        let signing_key = ActiveSigningKey {
+            kid,
+            key: PKeyWithDigest {
+                digest: MessageDigest::sha256(),
+                key: PKey::from_rsa(rsa).unwrap(),
+            },
+        };
+        (signing_key, public_key)
+    }
+
+    // Distribution 3 looks the key up in its JWKS by the kid, which must match the published key.
+    #[test]
+    fn registry_token_is_verifiable_with_the_published_key() {
+        let (signing_key, public_key) = signing_key();
+        let kid = signing_key.kid.clone();
+
+        let token = sign_registry_token(claims(), signing_key).unwrap();
+
+        let unverified: Token<RegistryTokenHeader, Value, Unverified> =
+            Token::parse_unverified(&token).unwrap();
+        assert_eq!(unverified.header().kid, kid);
+        assert_eq!(unverified.header().alg, AlgorithmType::Rs256);
+        assert_eq!(unverified.header().typ, REGISTRY_TOKEN_TYPE);
+
+        let verified = unverified.verify_with_key(&public_key).unwrap();
+        assert_eq!(verified.claims()["aud"], "registry.example.com");
+    }
+
+    // ID token hints are parsed with the standard header, which has no room for our own token types.
+    #[test]
+    fn registry_token_is_not_an_id_token() {
+        let (signing_key, _) = signing_key();
+
+        let token = sign_registry_token(claims(), signing_key).unwrap();
+
+        assert!(Token::<Header, Value, Unverified>::parse_unverified(&token).is_err());
+    }
+}
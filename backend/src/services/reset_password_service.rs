@@ -8,7 +8,10 @@ use crate::{
     api::auth::session_guard,
     db::{login_details_repository, new_transaction, reset_password_repository, DB},
     models::password_reset::PasswordReset,
-    services::{email_service, email_service::EmailError, password_service},
+    services::{
+        backchannel_logout_service, email_service, email_service::EmailError, password_service,
+        signing_key_service::SigningKeyCache,
+    },
     util::{accounts_error::AccountsError, config::Config, uuid::uuid_from_sqlx},
 };
 
@@ -134,6 +137,7 @@ If you did not request this password reset you can safely ignore this email.
 
 pub async fn update_password(
     config: &State<Config>,
+    signing_keys: &SigningKeyCache,
     db_pool: &State<Pool<DB>>,
     redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
     email: String,
@@ -182,7 +186,8 @@ pub async fn update_password(
     reset_password_repository::delete_password_reset(&mut transaction, password_reset_code.id)
         .await?;
 
-    session_guard::reset_account_sessions(redis_pool, uuid_from_sqlx(account.account_id))
+    let account_id = uuid_from_sqlx(account.account_id);
+    let session_ids = session_guard::reset_account_sessions(redis_pool, account_id)
         .await
         .map_err(|err| {
             error!("Failed to reset account sessions, err: {}", err);
@@ -190,5 +195,20 @@ pub async fn update_password(
         })?;
 
     transaction.commit().await?;
+
+    // The password has already been changed, failing to notify the clients shouldn't undo that.
+    if let Err(err) = backchannel_logout_service::logout_sessions(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &session_ids,
+        account_id,
+    )
+    .await
+    {
+        error!("Failed to send backchannel logouts, err: {}", err);
+    }
+
     Ok(())
 }
@@ -0,0 +1,371 @@
+use aes_gcm::aead::Aead;
+use aes_gcm::Nonce;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use jwt::PKeyWithDigest;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private, Public};
+use openssl::rsa::Rsa;
+use openssl::sha::sha256;
+use serde::Serialize;
+use sqlx::{Pool, Transaction};
+use std::collections::HashMap;
+use std::num::ParseIntError;
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    db::{new_transaction, signing_key_repository, DB},
+    models::signing_key::SigningKey,
+    util::{accounts_error::AccountsError, config::Config},
+};
+
+use super::password_service::{from_hex, to_hex};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SigningKeyError {
+    #[error("There is no active signing key")]
+    NoActiveKey,
+    #[error("Failed to encrypt/decrypt the private key")]
+    Encryption,
+    #[error("Unsupported signing algorithm `{0}`")]
+    UnsupportedAlgorithm(String),
+    #[error("OpenSSL error stack")]
+    OpenSSLError(#[from] openssl::error::ErrorStack),
+    #[error("Failed to parse hex string")]
+    HexError(#[from] ParseIntError),
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+pub const ALGORITHM_RS256: &str = "RS256";
+const RSA_KEY_BITS: u32 = 2048;
+
+// 30 days
+const KEY_ROTATION_MINUTES: i64 = 60 * 24 * 30;
+// 1 day, must be longer than the lifetime of any token signed by the keys.
+const RETIRED_KEY_PUBLISHED_MINUTES: u64 = 60 * 24;
+/// How long relying parties may cache the published keys.
+pub const JWKS_MAX_AGE_SECONDS: i64 = 60 * 60 * 24;
+// A new key is published for a full JWKS cache interval before it is used,
+// so that relying parties which cached the keys before it was added can still verify its tokens.
+const NEXT_KEY_PUBLISHED_MINUTES: i64 = JWKS_MAX_AGE_SECONDS / 60;
+
+/// The key that should currently be used to sign tokens.
+pub struct ActiveSigningKey {
+    pub kid: String,
+    pub key: PKeyWithDigest<Private>,
+}
+
+/// A public key in the JSON Web Key format (RFC 7517).
+#[derive(Serialize, Clone, Debug)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    pub alg: String,
+    pub kid: String,
+    pub n: String,
+    pub e: String,
+}
+
+const KEY_TYPE_RSA: &str = "RSA";
+const KEY_USE_SIGNATURE: &str = "sig";
+
+/// The decrypted and parsed signing keys, so that they don't have to be loaded for every token.
+/// Cleared whenever the keys are rotated, after which they are loaded again on use.
+#[derive(Clone, Default)]
+pub struct SigningKeyCache {
+    inner: Arc<SigningKeyCacheInner>,
+}
+
+#[derive(Default)]
+struct SigningKeyCacheInner {
+    active: RwLock<Option<(String, PKey<Private>)>>,
+    verifying: RwLock<HashMap<String, PKey<Public>>>,
+}
+
+impl SigningKeyCache {
+    fn get_active(&self) -> Option<ActiveSigningKey> {
+        let active = self
+            .inner
+            .active
+            .read()
+            .expect("Signing key cache poisoned");
+        active.as_ref().map(|(kid, key)| ActiveSigningKey {
+            kid: kid.clone(),
+            key: PKeyWithDigest {
+                digest: MessageDigest::sha256(),
+                key: key.clone(),
+            },
+        })
+    }
+
+    fn set_active(&self, kid: String, key: PKey<Private>) {
+        *self
+            .inner
+            .active
+            .write()
+            .expect("Signing key cache poisoned") = Some((kid, key));
+    }
+
+    fn get_verifying(&self, kid: &str) -> Option<PKeyWithDigest<Public>> {
+        let verifying = self
+            .inner
+            .verifying
+            .read()
+            .expect("Signing key cache poisoned");
+        verifying.get(kid).map(|key| PKeyWithDigest {
+            digest: MessageDigest::sha256(),
+            key: key.clone(),
+        })
+    }
+
+    fn set_verifying(&self, kid: String, key: PKey<Public>) {
+        self.inner
+            .verifying
+            .write()
+            .expect("Signing key cache poisoned")
+            .insert(kid, key);
+    }
+
+    fn clear(&self) {
+        *self
+            .inner
+            .active
+            .write()
+            .expect("Signing key cache poisoned") = None;
+        self.inner
+            .verifying
+            .write()
+            .expect("Signing key cache poisoned")
+            .clear();
+    }
+}
+
+pub async fn get_signing_key(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+) -> Result<ActiveSigningKey, SigningKeyError> {
+    if let Some(active_key) = signing_keys.get_active() {
+        return Ok(active_key);
+    }
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let signing_key = signing_key_repository::get_active(&mut transaction)
+        .await?
+        .ok_or(SigningKeyError::NoActiveKey)?;
+
+    transaction.commit().await?;
+
+    if signing_key.algorithm != ALGORITHM_RS256 {
+        return Err(SigningKeyError::UnsupportedAlgorithm(signing_key.algorithm));
+    }
+
+    let nonce_bytes = from_hex(signing_key.private_key_nonces)?;
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let encrypted_private_key = from_hex(signing_key.private_key)?;
+    let private_key_pem = config
+        .pepper_cipher
+        .decrypt(nonce, encrypted_private_key.as_ref())
+        .map_err(|err| {
+            error!(
+                "Failed to decrypt signing key {}, err: {}",
+                signing_key.kid, err
+            );
+            SigningKeyError::Encryption
+        })?;
+
+    let key = PKey::private_key_from_pem(&private_key_pem)?;
+    signing_keys.set_active(signing_key.kid.clone(), key.clone());
+
+    Ok(ActiveSigningKey {
+        kid: signing_key.kid,
+        key: PKeyWithDigest {
+            digest: MessageDigest::sha256(),
+            key,
+        },
+    })
+}
+
+/// Returns the public keys that tokens might currently be signed with, including the next key.
+pub async fn get_published_keys(db_pool: &Pool<DB>) -> Result<Vec<Jwk>, SigningKeyError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let signing_keys =
+        signing_key_repository::get_published(&mut transaction, RETIRED_KEY_PUBLISHED_MINUTES)
+            .await?;
+
+    transaction.commit().await?;
+
+    Ok(signing_keys
+        .iter()
+        .map(to_jwk)
+        .collect::<Result<Vec<Jwk>, _>>()?)
+}
+
+/// Returns the published key with the given key id, to verify tokens that were signed by us.
+pub async fn get_verifying_key(
+    db_pool: &Pool<DB>,
+    signing_keys: &SigningKeyCache,
+    kid: &str,
+) -> Result<Option<PKeyWithDigest<Public>>, SigningKeyError> {
+    if let Some(key) = signing_keys.get_verifying(kid) {
+        return Ok(Some(key));
+    }
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let published_keys =
+        signing_key_repository::get_published(&mut transaction, RETIRED_KEY_PUBLISHED_MINUTES)
+            .await?;
+
+    transaction.commit().await?;
+
+    let Some(signing_key) = published_keys.into_iter().find(|key| key.kid == kid) else {
+        return Ok(None);
+    };
+
+    if signing_key.algorithm != ALGORITHM_RS256 {
+        return Err(SigningKeyError::UnsupportedAlgorithm(signing_key.algorithm));
+    }
+
+    let key = PKey::public_key_from_pem(signing_key.public_key.as_bytes())?;
+    signing_keys.set_verifying(signing_key.kid, key.clone());
+
+    Ok(Some(PKeyWithDigest {
+        digest: MessageDigest::sha256(),
+        key,
+    }))
+}
+
+/// The JWK thumbprint (RFC 7638) of an RSA key, which is used as the key id.
+pub fn jwk_thumbprint(n: &str, e: &str) -> String {
+    let thumbprint_input = format!(r#"{{"e":"{e}","kty":"{KEY_TYPE_RSA}","n":"{n}"}}"#);
+    URL_SAFE_NO_PAD.encode(sha256(thumbprint_input.as_bytes()))
+}
+
+fn to_jwk(signing_key: &SigningKey) -> Result<Jwk, openssl::error::ErrorStack> {
+    let public_key = Rsa::public_key_from_pem(signing_key.public_key.as_bytes())?;
+
+    Ok(Jwk {
+        kty: KEY_TYPE_RSA.to_string(),
+        key_use: KEY_USE_SIGNATURE.to_string(),
+        alg: signing_key.algorithm.clone(),
+        kid: signing_key.kid.clone(),
+        n: URL_SAFE_NO_PAD.encode(public_key.n().to_vec()),
+        e: URL_SAFE_NO_PAD.encode(public_key.e().to_vec()),
+    })
+}
+
+/// Makes sure that there is an active signing key that is younger than the rotation period,
+/// publishes the next key ahead of its use and removes retired keys that are no longer needed to verify any tokens.
+pub async fn rotate_signing_keys(
+    db_pool: &Pool<DB>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+) -> Result<(), SigningKeyError> {
+    let mut transaction = new_transaction(db_pool).await?;
+
+    // Every instance rotates the keys, without the lock they could each create a new key.
+    signing_key_repository::lock_rotation(&mut transaction).await?;
+
+    let now = Utc::now();
+    let active_key = signing_key_repository::get_active(&mut transaction).await?;
+    let next_key = signing_key_repository::get_next(&mut transaction).await?;
+
+    let activate_key = match (active_key, next_key) {
+        // Nothing has been signed yet, so there are no cached keys to wait for.
+        (None, None) => Some(create_signing_key(&mut transaction, config).await?),
+        (None, Some(next_key)) => Some(next_key),
+        (Some(active_key), next_key) => {
+            let rotate_at = active_key.activated_at.unwrap_or(active_key.created_at)
+                + Duration::minutes(KEY_ROTATION_MINUTES);
+            match next_key {
+                None if now > rotate_at - Duration::minutes(NEXT_KEY_PUBLISHED_MINUTES) => {
+                    create_signing_key(&mut transaction, config).await?;
+                    None
+                }
+                Some(next_key)
+                    if now > rotate_at
+                        && now
+                            > next_key.created_at
+                                + Duration::minutes(NEXT_KEY_PUBLISHED_MINUTES) =>
+                {
+                    Some(next_key)
+                }
+                _ => None,
+            }
+        }
+    };
+
+    if let Some(key) = activate_key {
+        signing_key_repository::activate(&mut transaction, key.id).await?;
+        info!("Activated signing key {}", key.kid);
+    }
+
+    // Only the newest active key is kept, which also retires any left by concurrent rotations before the lock.
+    if let Some(active_key) = signing_key_repository::get_active(&mut transaction).await? {
+        let retired_keys =
+            signing_key_repository::retire_active_except(&mut transaction, active_key.id).await?;
+        for retired_key in retired_keys {
+            info!("Retired signing key {}", retired_key.kid);
+        }
+    }
+
+    let deleted_keys =
+        signing_key_repository::delete_retired(&mut transaction, RETIRED_KEY_PUBLISHED_MINUTES)
+            .await?;
+    if !deleted_keys.is_empty() {
+        info!("Deleted {} retired signing keys", deleted_keys.len());
+    }
+
+    transaction.commit().await?;
+
+    // The keys might also have been rotated by another instance, so always reload them.
+    signing_keys.clear();
+
+    Ok(())
+}
+
+/// Creates a new key, which is published but not used until it is activated.
+async fn create_signing_key(
+    transaction: &mut Transaction<'_, DB>,
+    config: &Config,
+) -> Result<SigningKey, SigningKeyError> {
+    let rsa = Rsa::generate(RSA_KEY_BITS)?;
+    let n = URL_SAFE_NO_PAD.encode(rsa.n().to_vec());
+    let e = URL_SAFE_NO_PAD.encode(rsa.e().to_vec());
+
+    let kid = jwk_thumbprint(&n, &e);
+
+    let private_key_pem = rsa.private_key_to_pem()?;
+    let public_key_pem = String::from_utf8_lossy(&rsa.public_key_to_pem()?).to_string();
+
+    let nonce_arr: [u8; 12] = rand::random();
+    let nonce = Nonce::from_slice(&nonce_arr);
+    let encrypted_private_key = config
+        .pepper_cipher
+        .encrypt(nonce, private_key_pem.as_slice())
+        .map_err(|err| {
+            error!("Failed to encrypt new signing key, err: {}", err);
+            SigningKeyError::Encryption
+        })?;
+
+    let signing_key = signing_key_repository::insert(
+        transaction,
+        kid.clone(),
+        ALGORITHM_RS256.to_string(),
+        to_hex(&encrypted_private_key),
+        to_hex(&nonce_arr),
+        public_key_pem,
+    )
+    .await?;
+
+    info!("Created new signing key {kid}");
+
+    Ok(signing_key)
+}
@@ -0,0 +1,166 @@
+use rocket::State;
+use sqlx::Pool;
+use uuid::Uuid;
+
+use crate::{
+    db::{new_transaction, oauth_client_repository, token_exchange_policy_repository, DB},
+    models::token_exchange_policy::TokenExchangePolicy,
+    util::{accounts_error::AccountsError, scope},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenExchangeError {
+    #[error("The client may not exchange tokens for the audience")]
+    InvalidTarget,
+    #[error("Requested scope is not allowed for the audience")]
+    InvalidScope,
+    #[error("The provided ID is not a valid UUID")]
+    InvalidId,
+    #[error("No client with that ID exists")]
+    ClientIdNotFound,
+    #[error("No policy with that ID exists for the client")]
+    PolicyIdNotFound,
+    #[error("The audience must be another existing client")]
+    InvalidPolicy,
+    #[error("The client already has a policy for the audience")]
+    PolicyExists,
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Accounts error")]
+    AccountsError(#[from] AccountsError),
+}
+
+/// The scopes of a token exchanged for the audience, which can only narrow the subject token's scopes
+/// down to those the client's policy for the audience allows.
+/// Without requested scopes the exchanged token gets every scope it can.
+pub async fn resolve_exchange_scopes(
+    db_pool: &Pool<DB>,
+    client_id: &str,
+    audience: &str,
+    subject_scopes: &[String],
+    requested_scopes: Option<Vec<String>>,
+) -> Result<Vec<String>, TokenExchangeError> {
+    let mut transaction = new_transaction(db_pool).await?;
+    let policy =
+        token_exchange_policy_repository::get_by_audience(&mut transaction, client_id, audience)
+            .await?
+            .ok_or(TokenExchangeError::InvalidTarget)?;
+    transaction.commit().await?;
+
+    let allowed_scopes: Vec<String> = subject_scopes
+        .iter()
+        .filter(|subject_scope| scope::has_scope(&policy.scopes, subject_scope))
+        .cloned()
+        .collect();
+
+    let Some(requested_scopes) = requested_scopes else {
+        return Ok(allowed_scopes);
+    };
+
+    if let Some(scope) = requested_scopes
+        .iter()
+        .find(|scope| !scope::has_scope(&allowed_scopes, scope))
+    {
+        error!(
+            "Client {} requested scope '{}' which can't be exchanged for audience {}",
+            client_id, scope, audience
+        );
+        return Err(TokenExchangeError::InvalidScope);
+    }
+
+    Ok(requested_scopes)
+}
+
+pub async fn get_policies(
+    db_pool: &State<Pool<DB>>,
+    id: String,
+) -> Result<Vec<TokenExchangePolicy>, TokenExchangeError> {
+    let id = parse_id(&id).ok_or(TokenExchangeError::InvalidId)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    let client = oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(TokenExchangeError::ClientIdNotFound)?;
+    let policies =
+        token_exchange_policy_repository::get_by_client(&mut transaction, &client.client_id)
+            .await?;
+    transaction.commit().await?;
+
+    Ok(policies)
+}
+
+/// Lets the client exchange access tokens for the audience, limited to the given scopes.
+pub async fn create_policy(
+    db_pool: &State<Pool<DB>>,
+    id: String,
+    audience: String,
+    scopes: Vec<String>,
+) -> Result<TokenExchangePolicy, TokenExchangeError> {
+    let id = parse_id(&id).ok_or(TokenExchangeError::InvalidId)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let client = oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(TokenExchangeError::ClientIdNotFound)?;
+
+    if client.client_id == audience
+        || oauth_client_repository::get_by_client_id(&mut transaction, &audience)
+            .await?
+            .is_none()
+    {
+        return Err(TokenExchangeError::InvalidPolicy);
+    }
+
+    if token_exchange_policy_repository::get_by_audience(
+        &mut transaction,
+        &client.client_id,
+        &audience,
+    )
+    .await?
+    .is_some()
+    {
+        return Err(TokenExchangeError::PolicyExists);
+    }
+
+    let policy = token_exchange_policy_repository::insert(
+        &mut transaction,
+        client.client_id,
+        audience,
+        scopes,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(policy)
+}
+
+pub async fn delete_policy(
+    db_pool: &State<Pool<DB>>,
+    id: String,
+    policy_id: String,
+) -> Result<(), TokenExchangeError> {
+    let id = parse_id(&id).ok_or(TokenExchangeError::InvalidId)?;
+    let policy_id = parse_id(&policy_id).ok_or(TokenExchangeError::InvalidId)?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+
+    let client = oauth_client_repository::get_by_id(&mut transaction, id)
+        .await?
+        .ok_or(TokenExchangeError::ClientIdNotFound)?;
+
+    token_exchange_policy_repository::delete(&mut transaction, &client.client_id, policy_id)
+        .await?
+        .ok_or(TokenExchangeError::PolicyIdNotFound)?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+fn parse_id(id: &str) -> Option<Uuid> {
+    Uuid::parse_str(id)
+        .map_err(|err| error!("Failed to parse id as UUID, err {}", err))
+        .ok()
+}
@@ -29,28 +29,32 @@ pub enum UserInfoError {
 pub struct UserInfo {
     pub account: Account,
     pub email: String,
+    pub email_verified: bool,
 }
 
 pub async fn get_user_info(
     db_pool: &State<Pool<DB>>,
     access_token: AccessToken,
 ) -> Result<UserInfo, UserInfoError> {
+    // Client credentials tokens don't belong to any user.
+    let account_id = access_token
+        .account_id
+        .ok_or(UserInfoError::InvalidAccessToken)?;
+
     let mut transaction = new_transaction(db_pool).await?;
 
-    let account =
-        account_repository::get_account(&mut transaction, uuid_to_sqlx(access_token.account_id))
-            .await?
-            .ok_or(UserInfoError::AccountNotFound)?;
+    let account = account_repository::get_account(&mut transaction, uuid_to_sqlx(account_id))
+        .await?
+        .ok_or(UserInfoError::AccountNotFound)?;
 
-    let login_details = login_details_repository::get_by_account_id(
-        &mut transaction,
-        uuid_to_sqlx(access_token.account_id),
-    )
-    .await?
-    .ok_or(UserInfoError::LoginDetailsNotFound)?;
+    let login_details =
+        login_details_repository::get_by_account_id(&mut transaction, uuid_to_sqlx(account_id))
+            .await?
+            .ok_or(UserInfoError::LoginDetailsNotFound)?;
 
     Ok(UserInfo {
         account,
         email: login_details.email,
+        email_verified: login_details.activated_at.is_some(),
     })
 }
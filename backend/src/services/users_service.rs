@@ -1,16 +1,23 @@
+use mobc_redis::RedisConnectionManager;
 use rocket::State;
-use sqlx::Pool;
+use sqlx::{types::Uuid, Pool};
 
 use crate::{
+    api::auth::session_guard,
     db::{account_repository, new_transaction, DB},
     models::account::Account,
-    util::accounts_error::AccountsError,
+    services::{backchannel_logout_service, signing_key_service::SigningKeyCache},
+    util::{accounts_error::AccountsError, config::Config, uuid::uuid_from_sqlx},
 };
 
 #[derive(Debug, thiserror::Error)]
 pub enum UsersError {
     #[error("An internal error occured")]
     Internal,
+    #[error("Invalid account id")]
+    InvalidId,
+    #[error("No account with that id")]
+    AccountNotFound,
 }
 
 impl From<sqlx::Error> for UsersError {
@@ -37,3 +44,45 @@ pub async fn get_all_users(db_pool: &State<Pool<DB>>) -> Result<Vec<Account>, Us
 
     Ok(accs)
 }
+
+/// Ends all of the user's sessions and notifies the clients they were used with.
+pub async fn logout_user(
+    db_pool: &State<Pool<DB>>,
+    redis_pool: &State<mobc::Pool<RedisConnectionManager>>,
+    config: &Config,
+    signing_keys: &SigningKeyCache,
+    id: String,
+) -> Result<(), UsersError> {
+    let id = Uuid::parse_str(&id).map_err(|err| {
+        error!("Failed to parse account id as UUID, err {}", err);
+        UsersError::InvalidId
+    })?;
+
+    let mut transaction = new_transaction(db_pool).await?;
+    account_repository::get_account(&mut transaction, id)
+        .await?
+        .ok_or(UsersError::AccountNotFound)?;
+    transaction.commit().await?;
+
+    let account_id = uuid_from_sqlx(id);
+    let session_ids = session_guard::reset_account_sessions(redis_pool, account_id)
+        .await
+        .map_err(|err| {
+            error!("Failed to reset account sessions, err: {}", err);
+            UsersError::Internal
+        })?;
+
+    backchannel_logout_service::logout_sessions(
+        db_pool,
+        redis_pool,
+        config,
+        signing_keys,
+        &session_ids,
+        account_id,
+    )
+    .await
+    .map_err(|err| {
+        error!("Failed to send backchannel logouts, err: {}", err);
+        UsersError::Internal
+    })
+}
@@ -38,6 +38,11 @@ pub struct Config {
     pub offline_mode: bool,
     pub redis_url: String,
     pub log_db_statements: bool,
+    /// The initial access token required to register clients dynamically,
+    /// registration is disabled if it isn't set.
+    pub client_registration_token: Option<String>,
+    /// Requires every client to use pushed authorization requests, rather than only the clients set up to.
+    pub require_pushed_authorization_requests: bool,
 }
 
 impl Config {
@@ -78,6 +83,10 @@ impl Config {
             offline_mode: load_env_bool("OFFLINE_MODE")?,
             redis_url: load_env_str("REDIS_URL")?,
             log_db_statements: load_env_bool("LOG_DB_STATEMENTS")?,
+            client_registration_token: load_optional_env_str("CLIENT_REGISTRATION_TOKEN")?,
+            require_pushed_authorization_requests: load_optional_env_bool(
+                "REQUIRE_PUSHED_AUTHORIZATION_REQUESTS",
+            )?,
         })
     }
 }
@@ -92,8 +101,28 @@ fn load_env_str(key: &str) -> ConfigResult<String> {
     Ok(var)
 }
 
+fn load_optional_env_str(key: &str) -> ConfigResult<Option<String>> {
+    match env::var(key) {
+        Ok(var) if var.is_empty() => Ok(None),
+        Ok(var) => Ok(Some(var)),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
 fn load_env_bool(key: &str) -> ConfigResult<bool> {
-    let var = load_env_str(key)?;
+    parse_bool(load_env_str(key)?)
+}
+
+/// Defaults to false if the variable isn't set.
+fn load_optional_env_bool(key: &str) -> ConfigResult<bool> {
+    match load_optional_env_str(key)? {
+        Some(var) => parse_bool(var),
+        None => Ok(false),
+    }
+}
+
+fn parse_bool(var: String) -> ConfigResult<bool> {
     match var.as_str() {
         "false" => Ok(false),
         "true" => Ok(true),
@@ -1,3 +1,6 @@
 pub mod accounts_error;
 pub mod config;
+pub mod pkce;
+pub mod redirect_uri;
+pub mod scope;
 pub mod uuid;
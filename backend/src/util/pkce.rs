@@ -0,0 +1,121 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use openssl::{memcmp, sha::sha256};
+use serde::{Deserialize, Serialize};
+
+pub const CODE_CHALLENGE_METHOD_PLAIN: &str = "plain";
+pub const CODE_CHALLENGE_METHOD_S256: &str = "S256";
+
+const MIN_LENGTH: usize = 43;
+const MAX_LENGTH: usize = 128;
+
+/// Proof Key for Code Exchange (RFC 7636) challenge sent by the client when requesting authorization.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CodeChallenge {
+    pub challenge: String,
+    pub method: CodeChallengeMethod,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeChallengeMethod {
+    Plain,
+    S256,
+}
+
+impl CodeChallengeMethod {
+    /// Parses the code_challenge_method parameter, which defaults to plain if not provided.
+    pub fn parse(method: Option<&str>) -> Option<CodeChallengeMethod> {
+        match method {
+            None | Some(CODE_CHALLENGE_METHOD_PLAIN) => Some(CodeChallengeMethod::Plain),
+            Some(CODE_CHALLENGE_METHOD_S256) => Some(CodeChallengeMethod::S256),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Checks that a code challenge or verifier only contains unreserved characters
+/// and is between 43 and 128 characters long.
+pub fn is_valid_code(code: &str) -> bool {
+    (MIN_LENGTH..=MAX_LENGTH).contains(&code.len())
+        && code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+}
+
+impl CodeChallenge {
+    pub fn verify(&self, code_verifier: &str) -> bool {
+        if !is_valid_code(code_verifier) {
+            return false;
+        }
+
+        let expected = match self.method {
+            CodeChallengeMethod::Plain => code_verifier.to_string(),
+            CodeChallengeMethod::S256 => URL_SAFE_NO_PAD.encode(sha256(code_verifier.as_bytes())),
+        };
+
+        expected.len() == self.challenge.len()
+            && memcmp::eq(expected.as_bytes(), self.challenge.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7636 appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const S256_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    fn challenge(challenge: &str, method: CodeChallengeMethod) -> CodeChallenge {
+        CodeChallenge {
+            challenge: challenge.to_string(),
+            method,
+        }
+    }
+
+    #[test]
+    fn verifies_rfc_7636_example() {
+        assert!(challenge(S256_CHALLENGE, CodeChallengeMethod::S256).verify(VERIFIER));
+    }
+
+    #[test]
+    fn rejects_wrong_verifier() {
+        let other = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXK";
+        assert!(!challenge(S256_CHALLENGE, CodeChallengeMethod::S256).verify(other));
+    }
+
+    #[test]
+    fn s256_challenge_is_not_accepted_as_plain_verifier() {
+        assert!(!challenge(S256_CHALLENGE, CodeChallengeMethod::S256).verify(S256_CHALLENGE));
+    }
+
+    #[test]
+    fn verifies_plain() {
+        assert!(challenge(VERIFIER, CodeChallengeMethod::Plain).verify(VERIFIER));
+        assert!(!challenge(VERIFIER, CodeChallengeMethod::Plain).verify(S256_CHALLENGE));
+    }
+
+    #[test]
+    fn rejects_invalid_verifiers() {
+        let too_short = &VERIFIER[..42];
+        assert!(!challenge(too_short, CodeChallengeMethod::Plain).verify(too_short));
+
+        let too_long = "a".repeat(129);
+        assert!(!challenge(&too_long, CodeChallengeMethod::Plain).verify(&too_long));
+
+        let reserved = format!("{}+", &VERIFIER[..42]);
+        assert!(!challenge(&reserved, CodeChallengeMethod::Plain).verify(&reserved));
+    }
+
+    #[test]
+    fn parses_method() {
+        assert_eq!(
+            CodeChallengeMethod::parse(None),
+            Some(CodeChallengeMethod::Plain)
+        );
+        assert_eq!(
+            CodeChallengeMethod::parse(Some("S256")),
+            Some(CodeChallengeMethod::S256)
+        );
+        assert_eq!(CodeChallengeMethod::parse(Some("s256")), None);
+    }
+}
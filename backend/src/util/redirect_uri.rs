@@ -0,0 +1,231 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use reqwest::Url;
+use rocket::http::uri::Absolute;
+
+const LOOPBACK_HOSTS: [&str; 2] = ["127.0.0.1", "[::1]"];
+
+/// Checks that a redirect URI is an absolute URI without a fragment (RFC 6749 section 3.1.2).
+pub fn is_valid_redirect_uri(redirect_uri: &str) -> bool {
+    Absolute::parse(redirect_uri).is_ok() && !redirect_uri.contains('#')
+}
+
+/// Checks that a URI we send requests to, such as a backchannel logout URI, is an https URI
+/// that doesn't point at our own network.
+/// Parsed the same way as when sending the request, so that e.g. `https://2130706433` is seen as a loopback address.
+pub fn is_public_https_uri(uri: &str) -> bool {
+    let Ok(url) = Url::parse(uri) else {
+        return false;
+    };
+
+    if url.scheme() != "https" || url.fragment().is_some() {
+        return false;
+    }
+
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+
+    // IP addresses have already been normalized, with IPv6 addresses in brackets.
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => {
+            let domain = host.trim_end_matches('.');
+            domain != "localhost" && !domain.ends_with(".localhost")
+        }
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Shared address space used behind carrier-grade NAT (RFC 6598)
+        || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first_segment = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local addresses (fc00::/7) are the IPv6 private networks
+        || first_segment & 0xfe00 == 0xfc00
+        // Link-local unicast (fe80::/10)
+        || first_segment & 0xffc0 == 0xfe80)
+}
+
+/// Redirect URIs are matched exactly, except for loopback redirects used by native apps
+/// where the port is allowed to vary as it is picked by the app at runtime (RFC 8252 section 7.3).
+pub fn matches_registered(registered_uris: &[String], redirect_uri: &str) -> bool {
+    registered_uris
+        .iter()
+        .any(|registered| registered == redirect_uri || matches_loopback(registered, redirect_uri))
+}
+
+fn matches_loopback(registered: &str, redirect_uri: &str) -> bool {
+    let (Ok(registered), Ok(requested)) =
+        (Absolute::parse(registered), Absolute::parse(redirect_uri))
+    else {
+        return false;
+    };
+
+    let (Some(registered_authority), Some(requested_authority)) =
+        (registered.authority(), requested.authority())
+    else {
+        return false;
+    };
+
+    registered.scheme() == "http"
+        && requested.scheme() == "http"
+        && LOOPBACK_HOSTS.contains(&registered_authority.host())
+        && registered_authority.host() == requested_authority.host()
+        && registered_authority.user_info() == requested_authority.user_info()
+        && registered.path() == requested.path()
+        && registered.query() == requested.query()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(uris: &[&str]) -> Vec<String> {
+        uris.iter().map(|uri| uri.to_string()).collect()
+    }
+
+    #[test]
+    fn matches_exact_uri() {
+        let uris = registered(&["https://app.example.com/callback"]);
+        assert!(matches_registered(
+            &uris,
+            "https://app.example.com/callback"
+        ));
+    }
+
+    #[test]
+    fn rejects_prefix_and_suffix_tricks() {
+        let uris = registered(&["https://app.example.com/callback"]);
+        assert!(!matches_registered(
+            &uris,
+            "https://app.example.com/callback/evil"
+        ));
+        assert!(!matches_registered(
+            &uris,
+            "https://app.example.com/callback.evil.com"
+        ));
+        assert!(!matches_registered(
+            &uris,
+            "https://app.example.com.evil.com/callback"
+        ));
+        assert!(!matches_registered(
+            &uris,
+            "https://app.example.com/callbac"
+        ));
+        assert!(!matches_registered(
+            &uris,
+            "https://evil.com@app.example.com/callback"
+        ));
+    }
+
+    #[test]
+    fn rejects_added_query() {
+        let uris = registered(&["https://app.example.com/callback"]);
+        assert!(!matches_registered(
+            &uris,
+            "https://app.example.com/callback?next=https://evil.com"
+        ));
+    }
+
+    #[test]
+    fn rejects_changed_scheme_or_port() {
+        let uris = registered(&["https://app.example.com/callback"]);
+        assert!(!matches_registered(
+            &uris,
+            "http://app.example.com/callback"
+        ));
+        assert!(!matches_registered(
+            &uris,
+            "https://app.example.com:8443/callback"
+        ));
+    }
+
+    #[test]
+    fn loopback_port_may_vary() {
+        let uris = registered(&["http://127.0.0.1/callback", "http://[::1]:8080/callback"]);
+        assert!(matches_registered(&uris, "http://127.0.0.1:51234/callback"));
+        assert!(matches_registered(&uris, "http://[::1]:51234/callback"));
+    }
+
+    #[test]
+    fn loopback_must_otherwise_match() {
+        let uris = registered(&["http://127.0.0.1/callback"]);
+        assert!(!matches_registered(&uris, "http://127.0.0.1:51234/other"));
+        assert!(!matches_registered(
+            &uris,
+            "http://127.0.0.1:51234/callback?x=1"
+        ));
+        assert!(!matches_registered(
+            &uris,
+            "https://127.0.0.1:51234/callback"
+        ));
+        assert!(!matches_registered(&uris, "http://[::1]:51234/callback"));
+
+        let localhost = registered(&["http://localhost/callback"]);
+        assert!(!matches_registered(
+            &localhost,
+            "http://localhost:51234/callback"
+        ));
+    }
+
+    #[test]
+    fn validates_redirect_uris() {
+        assert!(is_valid_redirect_uri("https://app.example.com/callback"));
+        assert!(!is_valid_redirect_uri("/callback"));
+        assert!(!is_valid_redirect_uri(
+            "https://app.example.com/callback#fragment"
+        ));
+    }
+
+    #[test]
+    fn public_https_uri_must_use_https() {
+        assert!(is_public_https_uri("https://app.example.com/logout"));
+        assert!(is_public_https_uri("https://93.184.216.34/logout"));
+        assert!(!is_public_https_uri("http://app.example.com/logout"));
+        assert!(!is_public_https_uri("/logout"));
+    }
+
+    #[test]
+    fn public_https_uri_rejects_internal_hosts() {
+        for uri in [
+            "https://localhost/logout",
+            "https://api.localhost./logout",
+            "https://127.0.0.1/logout",
+            "https://2130706433/logout",
+            "https://0x7f.1/logout",
+            "https://0.0.0.0/logout",
+            "https://10.0.0.1/logout",
+            "https://172.16.0.1/logout",
+            "https://192.168.1.1/logout",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.64.0.1/logout",
+            "https://[::1]/logout",
+            "https://[::]/logout",
+            "https://[::ffff:127.0.0.1]/logout",
+            "https://[fd00::1]/logout",
+            "https://[fe80::1]/logout",
+        ] {
+            assert!(!is_public_https_uri(uri), "{uri}");
+        }
+    }
+}
@@ -0,0 +1,51 @@
+pub const SCOPE_OPENID: &str = "openid";
+pub const SCOPE_PROFILE: &str = "profile";
+pub const SCOPE_EMAIL: &str = "email";
+
+/// The OpenID Connect scopes supported by the server, clients may also be given custom scopes.
+pub const STANDARD_SCOPES: [&str; 3] = [SCOPE_OPENID, SCOPE_PROFILE, SCOPE_EMAIL];
+
+/// Parses a space-delimited scope parameter (RFC 6749 section 3.3) into a list of scopes.
+pub fn parse_scopes(scope: &str) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in scope.split_whitespace() {
+        if !scopes.iter().any(|s| s == scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    scopes
+}
+
+pub fn format_scopes(scopes: &[String]) -> String {
+    scopes.join(" ")
+}
+
+pub fn has_scope(scopes: &[String], scope: &str) -> bool {
+    scopes.iter().any(|s| s == scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_space_delimited_scopes() {
+        assert_eq!(parse_scopes("openid profile"), vec!["openid", "profile"]);
+    }
+
+    #[test]
+    fn ignores_extra_whitespace() {
+        assert_eq!(parse_scopes("  openid   email "), vec!["openid", "email"]);
+        assert!(parse_scopes("   ").is_empty());
+    }
+
+    #[test]
+    fn removes_duplicates_keeping_order() {
+        assert_eq!(parse_scopes("email openid email"), vec!["email", "openid"]);
+    }
+
+    #[test]
+    fn is_case_sensitive() {
+        assert_eq!(parse_scopes("openid OpenID"), vec!["openid", "OpenID"]);
+    }
+}
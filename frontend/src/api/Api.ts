@@ -69,11 +69,11 @@ export const Api = {
         cookie
       );
     },
-    create: (clientName: string, redirectUri: string) => {
+    create: (clientName: string, redirectUris: string[]) => {
       return handleResponse(
         axios.post<RawApiResponse<NewOAuthClient>>("/site/oauth_clients", {
           clientName: clientName,
-          redirectUri: redirectUri,
+          redirectUris: redirectUris,
         })
       );
     },
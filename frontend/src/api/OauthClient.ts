@@ -2,10 +2,18 @@ export interface OauthClient {
   id: string;
   clientName: string;
   clientId: string;
-  redirectUri: string;
+  redirectUris: string[];
+  scopes: string[];
+  requirePkce: boolean;
+  publicClient: boolean;
+  firstParty: boolean;
+  postLogoutRedirectUris: string[];
+  backchannelLogoutUri: string | null;
+  jwtAccessTokens: boolean;
+  requirePushedAuthorizationRequests: boolean;
 }
 
 export interface NewOAuthClient {
   clientId: string;
-  clientSecret: string;
+  clientSecret: string | null;
 }
@@ -1,6 +1,7 @@
 import { AuthorityLevel } from "./AuthorityLevel";
 
 export interface User {
+  id: string;
   firstName: string;
   lastName: string;
   authority: AuthorityLevel;
@@ -14,14 +14,14 @@ const REDIRECT_URI_ID = "REDIRECT_URI_ID";
 const CreateClient = () => {
   const router = useRouter();
   const [clientName, setClientName] = useState("");
-  const [redirectUri, setRedirectUri] = useState("");
+  const [redirectUris, setRedirectUris] = useState("");
   const [error, setError] = useState<undefined | string>(undefined);
   const { openModal } = useModal();
 
   const onSubmit = (e: FormEvent<HTMLFormElement>) => {
     e.preventDefault();
     Api.oauthClients
-      .create(clientName, redirectUri)
+      .create(clientName, redirectUris.split(/\s+/).filter((uri) => uri))
       .then((resp) => {
         openModal({
           title: "New Client info",
@@ -65,13 +65,13 @@ Client Secret: ${resp.data!!.clientSecret}`,
           />
         </div>
         <div className={styles.createClientRow}>
-          <label htmlFor={REDIRECT_URI_ID}>Redirect Uri:</label>
+          <label htmlFor={REDIRECT_URI_ID}>Redirect Uris (space separated):</label>
           <TextField
             id={REDIRECT_URI_ID}
             name={REDIRECT_URI_ID}
-            value={redirectUri}
+            value={redirectUris}
             onChange={(e) => {
-              setRedirectUri(e.target.value);
+              setRedirectUris(e.target.value);
             }}
           />
         </div>
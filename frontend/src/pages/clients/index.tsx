@@ -41,8 +41,8 @@ const Clients = ({error, clients}: ClientsProps) => {
                                     <p>{client.clientId}</p>
                                 </div>
                                 <div className={styles.row}>
-                                    <b>Redirect URI</b>
-                                    <p>{client.redirectUri}</p>
+                                    <b>Redirect URIs</b>
+                                    <p>{client.redirectUris.join(", ")}</p>
                                 </div>
                             </div>
                             <div>
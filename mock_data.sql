@@ -56,11 +56,16 @@ VALUES(
     );
 
 -- Insert oauth client with client_id: 'client_id' and client_secret: 'secret'
-INSERT INTO oauth_client(id, client_id, client_secret, client_name, redirect_uri)
+INSERT INTO oauth_client(id, client_id, client_name, redirect_uris)
 VALUES (
     'a58b3dae-91d9-46a3-971b-a6cda3e8f3f5',
     'client_id',
-    'secret',
     'Mock client name',
-    'http://localhost:3001/auth/callback'
+    ARRAY['http://localhost:3001/auth/callback']
+);
+-- The client secret is stored as the uppercase hex encoded sha256 hash of 'secret'
+INSERT INTO oauth_client_secret(oauth_client_id, secret_hash)
+VALUES (
+    'a58b3dae-91d9-46a3-971b-a6cda3e8f3f5',
+    '2BB80D537B1DA3E38BD30361AA855686BDE0EACD7162FEF6A25FE97BF527A25B'
 )